@@ -0,0 +1,333 @@
+use crate::command::transcode::encode;
+use crate::command::work::{
+    detect_content_type, read_local_work, read_selection, with_jsonp_suffix,
+    write_jsonp_file, write_jsonp_from_origin, write_preview_files, write_raw_files, with_work_locked,
+    Work, ORIGIN_DIR, PREVIEW_DIR,
+};
+use crate::util::transcode::{format_from_name, FORMAT_JPEG};
+use base64::{engine::general_purpose, Engine as _};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+const BRANDING_FILE: &str = "branding.json";
+const BRANDED_QUALITY: u8 = 90;
+// distance of the watermark to the face border, relative to the face width
+const MARGIN: f64 = 0.04;
+// branded copies of the faces in `origin/` for the raw layout
+pub const BRANDED_DIR: &str = "branded";
+
+// watermark burned into the faces the viewer shows and overlay added to index.html,
+// the faces in `origin/` always stay as downloaded
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Branding {
+    // path of a png or jpeg logo
+    pub logo: Option<String>,
+    // cube faces that get the watermark, `down` puts it on the floor
+    #[serde(default = "default_faces")]
+    pub faces: Vec<String>,
+    // `center`, `top-left`, `top-right`, `bottom-left` or `bottom-right`
+    #[serde(default = "default_position")]
+    pub position: String,
+    // watermark width relative to the face width
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default = "default_opacity")]
+    pub opacity: f64,
+    pub overlay_text: Option<String>,
+    #[serde(default)]
+    pub overlay_logo: bool,
+    #[serde(default = "default_overlay_position")]
+    pub overlay_position: String,
+}
+
+fn default_faces() -> Vec<String> {
+    vec![String::from("down")]
+}
+
+fn default_position() -> String {
+    String::from("center")
+}
+
+fn default_scale() -> f64 {
+    0.3
+}
+
+fn default_opacity() -> f64 {
+    0.8
+}
+
+fn default_overlay_position() -> String {
+    String::from("top-right")
+}
+
+pub fn read_branding(dir: &str) -> Option<Branding> {
+    let content = fs::read_to_string(Path::new(dir).join(BRANDING_FILE)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn read_logo(branding: &Branding) -> Result<Option<RgbaImage>, String> {
+    match &branding.logo {
+        Some(logo) if !logo.is_empty() => match image::open(logo) {
+            Ok(img) => Ok(Some(img.to_rgba8())),
+            Err(err) => Err(format!("open logo `{}` error: {}", logo, err)),
+        },
+        _ => Ok(None),
+    }
+}
+
+fn offset(position: &str, outer: u32, inner: u32, margin: u32, horizontal: bool) -> i64 {
+    let start = position.contains(if horizontal { "left" } else { "top" });
+    let end = position.contains(if horizontal { "right" } else { "bottom" });
+    if start {
+        margin as i64
+    } else if end {
+        outer as i64 - inner as i64 - margin as i64
+    } else {
+        (outer as i64 - inner as i64) / 2
+    }
+}
+
+pub fn watermark(face: &DynamicImage, logo: &RgbaImage, branding: &Branding) -> RgbaImage {
+    let mut base = face.to_rgba8();
+    let width = ((base.width() as f64 * branding.scale) as u32).max(1);
+    let height = ((logo.height() as f64 * width as f64 / logo.width() as f64) as u32).max(1);
+    let mut mark = imageops::resize(logo, width, height, FilterType::Lanczos3);
+    let opacity = branding.opacity.clamp(0.0, 1.0);
+    for pixel in mark.pixels_mut() {
+        pixel.0[3] = (pixel.0[3] as f64 * opacity).round() as u8;
+    }
+    let margin = (base.width() as f64 * MARGIN) as u32;
+    let x = offset(&branding.position, base.width(), width, margin, true);
+    let y = offset(&branding.position, base.height(), height, margin, false);
+    imageops::overlay(&mut base, &mark, x, y);
+    base
+}
+
+fn branded_faces(work: &Work, branding: &Branding) -> HashSet<String> {
+    let mut names: HashSet<String> = HashSet::new();
+    let wanted = |face: &str| branding.faces.iter().any(|f| f == face);
+    for item in work.panorama.list.iter() {
+        for (face, name) in [
+            ("right", &item.right),
+            ("left", &item.left),
+            ("front", &item.front),
+            ("back", &item.back),
+            ("up", &item.up),
+            ("down", &item.down),
+        ] {
+            if wanted(face) {
+                names.insert(name.clone());
+            }
+        }
+    }
+    names
+}
+
+// the logo of a work and the faces it goes on
+pub struct Watermark {
+    branding: Branding,
+    logo: RgbaImage,
+    faces: HashSet<String>,
+}
+
+impl Watermark {
+    pub fn applies(&self, name: &str) -> bool {
+        self.faces.contains(name)
+    }
+
+    // face `name` of `origin/` with the watermark, encoded in its own format
+    pub fn brand(&self, origin_path: &Path, name: &str) -> Result<Vec<u8>, String> {
+        let face = image::open(origin_path.join(name));
+        if let Err(err) = face {
+            return Err(format!("open face `{}` error: {}", name, err));
+        }
+        let branded = DynamicImage::ImageRgba8(watermark(&face.unwrap(), &self.logo, &self.branding));
+        encode(&branded, format_from_name(name).unwrap_or(FORMAT_JPEG), BRANDED_QUALITY)
+    }
+}
+
+// None when the work has no branding or the branding has no logo
+pub fn read_watermark(work: &Work, dir: &str) -> Result<Option<Watermark>, String> {
+    let branding = match read_branding(dir) {
+        Some(branding) => branding,
+        None => return Ok(None),
+    };
+    let logo = match read_logo(&branding)? {
+        Some(logo) => logo,
+        None => return Ok(None),
+    };
+    let faces = branded_faces(work, &branding);
+    Ok(Some(Watermark { branding, logo, faces }))
+}
+
+// rewrite the jsonp files of the selected faces with the watermark on top
+pub fn write_branded_faces(work: &Work, dir: &str) -> Result<(), String> {
+    let watermark = match read_watermark(work, dir)? {
+        Some(watermark) => watermark,
+        None => return Ok(()),
+    };
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let preview_path = Path::new(dir).join(PREVIEW_DIR);
+    for (index, (_, name)) in work.get_download_list().iter().enumerate() {
+        if !watermark.applies(name) {
+            continue;
+        }
+        let content = watermark.brand(&origin_path, name)?;
+        let jsonp_dest = with_jsonp_suffix(preview_path.join(name).to_str().unwrap(), index);
+        write_jsonp_file(&detect_content_type(&content, "", name), &content, &jsonp_dest, index)?;
+    }
+    Ok(())
+}
+
+// the raw viewer loads its faces straight from `origin/`, the branded ones are
+// written to `origin/branded/` and the returned work points at them
+pub fn write_raw_branded_faces(work: &Work, dir: &str) -> Result<Work, String> {
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let branded_path = origin_path.join(BRANDED_DIR);
+    if branded_path.exists() {
+        if let Err(err) = fs::remove_dir_all(&branded_path) {
+            return Err(format!("remove `{}` error: {}", branded_path.display(), err));
+        }
+    }
+    let watermark = match read_watermark(work, dir)? {
+        Some(watermark) => watermark,
+        None => return Ok(work.clone()),
+    };
+    let mut branded = work.clone();
+    let mut written: HashSet<String> = HashSet::new();
+    for item in branded.panorama.list.iter_mut() {
+        for face in [
+            &mut item.right,
+            &mut item.left,
+            &mut item.front,
+            &mut item.back,
+            &mut item.up,
+            &mut item.down,
+        ] {
+            if !watermark.applies(face) {
+                continue;
+            }
+            if !written.contains(face.as_str()) {
+                let dest = branded_path.join(face.as_str());
+                if let Err(err) = fs::create_dir_all(dest.parent().unwrap()) {
+                    return Err(err.to_string());
+                }
+                if let Err(err) = fs::write(&dest, watermark.brand(&origin_path, face)?) {
+                    return Err(format!("write `{}` error: {}", dest.display(), err));
+                }
+                written.insert(face.clone());
+            }
+            *face = format!("{}/{}", BRANDED_DIR, face);
+        }
+    }
+    Ok(branded)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn overlay_style(position: &str) -> String {
+    let vertical = if position.contains("bottom") { "bottom" } else { "top" };
+    let horizontal = if position.contains("left") { "left" } else { "right" };
+    format!(
+        "position:absolute;{}:16px;{}:16px;z-index:1000;pointer-events:none;display:flex;align-items:center;gap:8px;color:#fff;font-size:14px;text-shadow:0 1px 2px rgba(0,0,0,0.6);",
+        vertical, horizontal
+    )
+}
+
+// logo and text of the overlay, empty when the branding has neither
+pub fn overlay_html(branding: &Branding) -> Result<String, String> {
+    let mut overlay = String::new();
+    if branding.overlay_logo {
+        if let Some(logo) = &branding.logo {
+            let data = fs::read(logo);
+            if let Err(err) = data {
+                return Err(format!("read logo `{}` error: {}", logo, err));
+            }
+            let data = data.unwrap();
+            overlay.push_str(&format!(
+                "<img src=\"data:{};base64,{}\" style=\"height:40px;opacity:{};\">",
+                detect_content_type(&data, "", logo),
+                general_purpose::STANDARD.encode(&data),
+                branding.opacity.clamp(0.0, 1.0)
+            ));
+        }
+    }
+    if let Some(text) = &branding.overlay_text {
+        overlay.push_str(&format!("<span>{}</span>", escape_html(text)));
+    }
+    if overlay.is_empty() {
+        return Ok(overlay);
+    }
+    Ok(format!(
+        "<div class=\"branding-overlay\" style=\"{}\">{}</div>\n",
+        overlay_style(&branding.overlay_position),
+        overlay
+    ))
+}
+
+// the overlay goes right before `</body>` so it sits above the viewer
+pub fn brand_index_html(html: &str, branding: &Branding) -> Result<String, String> {
+    let overlay = overlay_html(branding)?;
+    if overlay.is_empty() {
+        return Ok(html.to_string());
+    }
+    match html.rfind("</body>") {
+        Some(end) => Ok(format!("{}{}{}", &html[..end], overlay, &html[end..])),
+        None => Ok(format!("{}{}", html, overlay)),
+    }
+}
+
+// index.html with the branding overlay of the work, other viewer files as embedded
+pub fn viewer_file(dir: &str, name: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    if name != "index.html" {
+        return Ok(data.to_vec());
+    }
+    match read_branding(dir) {
+        Some(branding) => Ok(brand_index_html(&String::from_utf8_lossy(data), &branding)?.into_bytes()),
+        None => Ok(data.to_vec()),
+    }
+}
+
+// save the branding of a work, None removes it, and regenerate its viewer
+pub fn set_branding(dir: &str, branding: Option<Branding>) -> Result<(), String> {
+    let path = Path::new(dir).join(BRANDING_FILE);
+    match &branding {
+        Some(branding) => {
+            read_logo(branding)?;
+            if let Err(err) = fs::write(&path, serde_json::to_string(branding).unwrap().as_bytes()) {
+                return Err(format!("write {} error: {}", BRANDING_FILE, err));
+            }
+        }
+        None => {
+            _ = fs::remove_file(&path);
+        }
+    }
+    let work = read_local_work(dir)?;
+    if read_selection(dir.to_string()).raw {
+        return write_raw_files(&work, dir);
+    }
+    // restore unbranded faces before the new branding is applied
+    write_jsonp_from_origin(&work, dir)?;
+    write_preview_files(&work, dir)
+}
+
+#[tauri::command]
+pub async fn set_work_branding(dir: String, branding: Option<Branding>) -> Result<String, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || with_work_locked(&dir, || set_branding(&dir, branding))).await;
+    match result {
+        Ok(Ok(_)) => Ok(String::from("ok")),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
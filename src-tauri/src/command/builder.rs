@@ -0,0 +1,272 @@
+use crate::command::panorama::convert_equirect_list;
+use crate::command::work::{
+    create_file_directory, write_jsonp_from_origin, write_preview_files, Initial, Model,
+    Observer, Panorama, Quaternion, Work, ORIGIN_DIR,
+};
+use crate::util::cube;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+const MODEL_DIR: &str = "model";
+const PICTURE_WIDTH: u32 = 800;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ObserverPosition {
+    position: Vec<f64>,
+    floor_index: Option<i64>,
+    quaternion: Option<Quaternion>,
+    standing_position: Option<Vec<f64>>,
+    accessible_nodes: Option<Vec<i64>>,
+    visible_nodes: Option<Vec<i64>>,
+}
+
+fn is_panorama_image(file_name: &str) -> bool {
+    let lower = file_name.to_lowercase();
+    lower.ends_with(".jpg") || lower.ends_with(".jpeg") || lower.ends_with(".png")
+}
+
+// equirectangular images of the folder, sorted by file name
+fn list_panorama_images(dir: &str) -> Result<Vec<String>, String> {
+    let entry = fs::read_dir(dir);
+    if let Err(err) = entry {
+        return Err(format!("read panorama dir `{}` error: {}", dir, err));
+    }
+    let mut list: Vec<String> = Vec::new();
+    for item in entry.unwrap() {
+        if let Ok(item) = item {
+            let file_name = item.file_name().to_string_lossy().to_string();
+            if item.path().is_file() && !file_name.starts_with('.') && is_panorama_image(&file_name) {
+                list.push(item.path().to_str().unwrap().to_string());
+            }
+        }
+    }
+    list.sort();
+    if list.is_empty() {
+        return Err(format!("no panorama image found in `{}`", dir));
+    }
+    Ok(list)
+}
+
+// positions are either a JSON array of `ObserverPosition` or CSV lines of `x,y,z[,floor_index]`
+pub fn read_positions(file_path: &str) -> Result<Vec<ObserverPosition>, String> {
+    let content = fs::read_to_string(file_path);
+    if let Err(err) = content {
+        return Err(format!("read positions `{}` error: {}", file_path, err));
+    }
+    let content = content.unwrap();
+    if content.trim_start().starts_with('[') {
+        return match serde_json::from_str(&content) {
+            Ok(list) => Ok(list),
+            Err(err) => Err(format!("json_decode positions error: {}", err)),
+        };
+    }
+    let mut list: Vec<ObserverPosition> = Vec::new();
+    let mut first = true;
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let header = first;
+        first = false;
+        let values: Result<Vec<f64>, _> = line.split(',').map(|v| v.trim().parse::<f64>()).collect();
+        if values.is_err() {
+            // the first line with content may be a header
+            if header {
+                continue;
+            }
+            return Err(format!("invalid position at line {}: `{}`", line_no + 1, line));
+        }
+        let values = values.unwrap();
+        if values.len() < 3 {
+            return Err(format!("invalid position at line {}: `{}`", line_no + 1, line));
+        }
+        list.push(ObserverPosition {
+            position: values[0..3].to_vec(),
+            floor_index: values.get(3).map(|v| *v as i64),
+            quaternion: None,
+            standing_position: None,
+            accessible_nodes: None,
+            visible_nodes: None,
+        });
+    }
+    Ok(list)
+}
+
+fn build_observers(count: usize, positions: &Vec<ObserverPosition>) -> Result<Vec<Observer>, String> {
+    if !positions.is_empty() && positions.len() != count {
+        return Err(format!(
+            "got {} positions for {} panoramas",
+            positions.len(),
+            count
+        ));
+    }
+    let mut observers: Vec<Observer> = Vec::new();
+    for index in 0..count {
+        let item = positions.get(index);
+        let position = item.map(|p| p.position.clone()).unwrap_or(vec![0.0, 0.0, 0.0]);
+        let floor_index = item.and_then(|p| p.floor_index).unwrap_or(0);
+        observers.push(Observer {
+            accessible_nodes: item.and_then(|p| p.accessible_nodes.clone()).unwrap_or_default(),
+            floor_index: floor_index,
+            index: index as i64,
+            offset_point_count: 0,
+            standing_position: item
+                .and_then(|p| p.standing_position.clone())
+                .unwrap_or(position.clone()),
+            position: position,
+            quaternion: item.and_then(|p| p.quaternion.clone()).unwrap_or(Quaternion {
+                w: 1.0,
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            }),
+            visible_nodes: item.and_then(|p| p.visible_nodes.clone()).unwrap_or_default(),
+        });
+    }
+    // without explicit connectivity every panorama can walk to the others on its floor
+    for index in 0..count {
+        if positions.get(index).map_or(false, |p| p.accessible_nodes.is_some()) {
+            continue;
+        }
+        let floor_index = observers[index].floor_index;
+        let nodes: Vec<i64> = observers
+            .iter()
+            .filter(|o| o.index != index as i64 && o.floor_index == floor_index)
+            .map(|o| o.index)
+            .collect();
+        if observers[index].visible_nodes.is_empty() {
+            observers[index].visible_nodes = nodes.clone();
+        }
+        observers[index].accessible_nodes = nodes;
+    }
+    Ok(observers)
+}
+
+fn write_picture(src: &str, dest: &str) -> Result<(), String> {
+    let source = image::open(src);
+    if let Err(err) = source {
+        return Err(format!("open `{}` failed: {}", src, err));
+    }
+    let source = source.unwrap().to_rgb8();
+    let height = (PICTURE_WIDTH as u64 * source.height() as u64 / source.width().max(1) as u64) as u32;
+    let picture = image::imageops::thumbnail(&source, PICTURE_WIDTH, height.max(1));
+    cube::write_jpeg(&picture, dest, 85)
+}
+
+pub fn build_work(
+    dir: &str,
+    panorama_dir: &str,
+    model_file: Option<String>,
+    positions_file: Option<String>,
+    face_size: u32,
+) -> Result<Work, String> {
+    let images = list_panorama_images(panorama_dir)?;
+    let positions = match positions_file {
+        Some(file_path) if !file_path.is_empty() => read_positions(&file_path)?,
+        _ => Vec::new(),
+    };
+    let observers = build_observers(images.len(), &positions)?;
+
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let panorama_list = convert_equirect_list(&images, origin_path.to_str().unwrap(), face_size)?;
+    write_picture(&images[0], origin_path.join("picture.jpg").to_str().unwrap())?;
+    write_picture(&images[0], origin_path.join("title_picture.jpg").to_str().unwrap())?;
+
+    let mut file_url = String::new();
+    if let Some(model_file) = model_file.filter(|f| !f.is_empty()) {
+        let file_name = Path::new(&model_file).file_name().unwrap().to_string_lossy().to_string();
+        file_url = format!("{}/{}", MODEL_DIR, file_name);
+        let dest = origin_path.join(&file_url);
+        create_file_directory(dest.to_str().unwrap())?;
+        if let Err(err) = fs::copy(&model_file, &dest) {
+            return Err(format!("copy model `{}` error: {}", model_file, err));
+        }
+    }
+
+    let work = Work {
+        base_url: String::new(),
+        initial: Initial {
+            flag_position: None,
+            fov: 95,
+            heading: None,
+            latitude: 0.0,
+            longitude: 0.0,
+            pano: Some(0),
+            pano_index: Some(0),
+        },
+        model: Model {
+            file_url: file_url,
+            material_base_url: String::new(),
+            material_textures: Vec::new(),
+            model_type: 0,
+        },
+        observers: observers,
+        panorama: Panorama {
+            count: panorama_list.len() as i64,
+            list: panorama_list,
+        },
+        picture_url: String::from("picture.jpg"),
+        title_picture_url: String::from("title_picture.jpg"),
+    };
+
+    if let Err(err) = fs::write(
+        Path::new(dir).join("input.json"),
+        serde_json::to_string(&work).unwrap().as_bytes(),
+    ) {
+        return Err(format!("write work input.json error:{}", err));
+    }
+    write_jsonp_from_origin(&work, dir)?;
+    write_preview_files(&work, dir)?;
+    Ok(work)
+}
+
+#[tauri::command]
+pub async fn build_local_work(
+    dir: String,
+    panorama_dir: String,
+    model_file: Option<String>,
+    positions_file: Option<String>,
+    face_size: u32,
+) -> Result<Work, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || {
+        build_work(&dir, &panorama_dir, model_file, positions_file, face_size)
+    })
+    .await;
+    match result {
+        Ok(Ok(work)) => Ok(work),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_positions;
+    use std::fs;
+
+    fn positions_of(name: &str, content: &str) -> Result<Vec<Vec<f64>>, String> {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, content).unwrap();
+        let result = read_positions(path.to_str().unwrap());
+        _ = fs::remove_file(&path);
+        result.map(|list| list.into_iter().map(|p| p.position).collect())
+    }
+
+    #[test]
+    fn csv_header_after_comments() {
+        let content = "\n# exported positions\nx,y,z,floor\n1,2,3,0\n4,5,6\n";
+        assert_eq!(
+            positions_of("builder_header.csv", content).unwrap(),
+            vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]
+        );
+    }
+
+    #[test]
+    fn csv_text_after_header_fails() {
+        assert!(positions_of("builder_invalid.csv", "x,y,z\n1,2,3\na,b,c\n").is_err());
+    }
+}
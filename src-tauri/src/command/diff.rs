@@ -0,0 +1,264 @@
+use crate::util::url::strip_query;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+
+pub const KIND_ADDED: &str = "added";
+pub const KIND_REMOVED: &str = "removed";
+pub const KIND_CHANGED: &str = "changed";
+// an index found more than once in a list, the extra item is on its side
+pub const KIND_DUPLICATE: &str = "duplicate";
+// positions and angles that differ below this are rounding noise
+const EPSILON: f64 = 1e-9;
+// longest value printed in the text report
+const REPORT_VALUE_LEN: usize = 120;
+
+// one difference, `path` like `observers[index=3].position[1]`
+#[derive(Serialize, Clone, Debug)]
+pub struct Difference {
+    pub path: String,
+    pub kind: String,
+    pub left: Option<Value>,
+    pub right: Option<Value>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WorkDiff {
+    pub identical: bool,
+    pub differences: Vec<Difference>,
+    // the differences one per line, for reading
+    pub report: String,
+}
+
+// a work directory or an input.json file
+fn read_work_value(path: &str) -> Result<Value, String> {
+    let mut file = Path::new(path).to_path_buf();
+    if file.is_dir() {
+        file = file.join("input.json");
+    }
+    let content = fs::read_to_string(&file);
+    if let Err(err) = content {
+        return Err(format!("read `{}` error: {}", file.display(), err));
+    }
+    // as stored, fields `Work` does not know about are compared too
+    match serde_json::from_str::<Value>(&content.unwrap()) {
+        Ok(value) if value.is_object() => Ok(value),
+        Ok(_) => Err(format!("`{}` is not a work", file.display())),
+        Err(err) => Err(format!("json_decode_work `{}` error: {}", file.display(), err)),
+    }
+}
+
+// urls and asset paths, other text keeps its `?` and `#`
+fn is_url(text: &str) -> bool {
+    text.contains('/') && !text.contains(char::is_whitespace)
+}
+
+fn same_scalar(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => (a - b).abs() <= EPSILON,
+            _ => a == b,
+        },
+        // signed urls only differ in their query
+        (Value::String(a), Value::String(b)) if is_url(a) && is_url(b) => strip_query(a) == strip_query(b),
+        _ => left == right,
+    }
+}
+
+// `index` of every element when all of them are objects that have one
+fn indexed(list: &Vec<Value>) -> Option<Vec<i64>> {
+    list.iter().map(|item| item.get("index").and_then(|i| i.as_i64())).collect()
+}
+
+fn push(out: &mut Vec<Difference>, path: &str, kind: &str, left: Option<&Value>, right: Option<&Value>) {
+    out.push(Difference {
+        path: path.to_string(),
+        kind: kind.to_string(),
+        left: left.cloned(),
+        right: right.cloned(),
+    });
+}
+
+fn diff_list<'a>(path: &str, left: &'a Vec<Value>, right: &'a Vec<Value>, out: &mut Vec<Difference>) {
+    // observers and panoramas are matched by index, not by position
+    if let (Some(left_index), Some(right_index)) = (indexed(left), indexed(right)) {
+        let all: BTreeSet<i64> = left_index.iter().chain(right_index.iter()).cloned().collect();
+        let with_index = |indexes: &Vec<i64>, list: &'a Vec<Value>, index: i64| -> Vec<&'a Value> {
+            indexes.iter().zip(list.iter()).filter(|(i, _)| **i == index).map(|(_, v)| v).collect()
+        };
+        for index in all {
+            let item_path = format!("{}[index={}]", path, index);
+            let a = with_index(&left_index, left, index);
+            let b = with_index(&right_index, right, index);
+            match (a.first(), b.first()) {
+                (Some(a), Some(b)) => diff_value(&item_path, a, b, out),
+                (Some(a), None) => push(out, &item_path, KIND_REMOVED, Some(a), None),
+                (None, Some(b)) => push(out, &item_path, KIND_ADDED, None, Some(b)),
+                (None, None) => {}
+            }
+            // the first one is compared, the others are reported
+            for item in a.iter().skip(1) {
+                push(out, &item_path, KIND_DUPLICATE, Some(item), None);
+            }
+            for item in b.iter().skip(1) {
+                push(out, &item_path, KIND_DUPLICATE, None, Some(item));
+            }
+        }
+        return;
+    }
+    for n in 0..left.len().max(right.len()) {
+        let item_path = format!("{}[{}]", path, n);
+        match (left.get(n), right.get(n)) {
+            (Some(a), Some(b)) => diff_value(&item_path, a, b, out),
+            (Some(a), None) => push(out, &item_path, KIND_REMOVED, Some(a), None),
+            (None, Some(b)) => push(out, &item_path, KIND_ADDED, None, Some(b)),
+            (None, None) => {}
+        }
+    }
+}
+
+fn diff_value(path: &str, left: &Value, right: &Value, out: &mut Vec<Difference>) {
+    match (left, right) {
+        (Value::Object(a), Value::Object(b)) => {
+            let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+            for key in keys {
+                let key_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match (a.get(key), b.get(key)) {
+                    (Some(x), Some(y)) => diff_value(&key_path, x, y, out),
+                    (Some(x), None) => push(out, &key_path, KIND_REMOVED, Some(x), None),
+                    (None, Some(y)) => push(out, &key_path, KIND_ADDED, None, Some(y)),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => diff_list(path, a, b, out),
+        _ => {
+            if !same_scalar(left, right) {
+                push(out, path, KIND_CHANGED, Some(left), Some(right));
+            }
+        }
+    }
+}
+
+fn short(value: &Option<Value>) -> String {
+    let text = match value {
+        Some(value) => value.to_string(),
+        None => String::new(),
+    };
+    if text.chars().count() <= REPORT_VALUE_LEN {
+        return text;
+    }
+    format!("{}...", text.chars().take(REPORT_VALUE_LEN).collect::<String>())
+}
+
+fn report(differences: &Vec<Difference>) -> String {
+    if differences.is_empty() {
+        return String::from("works are identical\n");
+    }
+    let mut text = format!("{} differences\n", differences.len());
+    for diff in differences.iter() {
+        let line = match diff.kind.as_str() {
+            KIND_ADDED => format!("+ {}: {}", diff.path, short(&diff.right)),
+            KIND_REMOVED => format!("- {}: {}", diff.path, short(&diff.left)),
+            KIND_DUPLICATE if diff.left.is_some() => format!("! {}: duplicate in left {}", diff.path, short(&diff.left)),
+            KIND_DUPLICATE => format!("! {}: duplicate in right {}", diff.path, short(&diff.right)),
+            _ => format!("~ {}: {} -> {}", diff.path, short(&diff.left), short(&diff.right)),
+        };
+        text.push_str(&line);
+        text.push('\n');
+    }
+    text
+}
+
+pub fn diff_work_values(left: &Value, right: &Value) -> WorkDiff {
+    let mut differences: Vec<Difference> = Vec::new();
+    diff_value("", left, right, &mut differences);
+    WorkDiff {
+        identical: differences.is_empty(),
+        report: report(&differences),
+        differences,
+    }
+}
+
+pub fn diff_work_files(left: &str, right: &str) -> Result<WorkDiff, String> {
+    Ok(diff_work_values(&read_work_value(left)?, &read_work_value(right)?))
+}
+
+// `left` and `right` are work directories or input.json files
+#[tauri::command]
+pub fn diff_works(left: String, right: String) -> Result<WorkDiff, InvokeError> {
+    match diff_work_files(&left, &right) {
+        Ok(diff) => Ok(diff),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_work_values, KIND_ADDED, KIND_CHANGED, KIND_DUPLICATE, KIND_REMOVED};
+    use serde_json::json;
+
+    fn kinds(left: &serde_json::Value, right: &serde_json::Value) -> Vec<(String, String)> {
+        diff_work_values(left, right)
+            .differences
+            .into_iter()
+            .map(|d| (d.path, d.kind))
+            .collect()
+    }
+
+    #[test]
+    fn signed_urls_are_equal() {
+        let left = json!({ "picture_url": "https://cdn.example.com/p.jpg?Expires=1&Signature=a", "title": "a?b" });
+        let right = json!({ "picture_url": "https://cdn.example.com/p.jpg?Expires=2&Signature=b", "title": "a?c" });
+        // plain text keeps its query
+        assert_eq!(kinds(&left, &right), vec![(String::from("title"), String::from(KIND_CHANGED))]);
+        let moved = json!({ "picture_url": "https://cdn.example.com/q.jpg?Expires=1", "title": "a?b" });
+        assert_eq!(kinds(&left, &moved), vec![(String::from("picture_url"), String::from(KIND_CHANGED))]);
+    }
+
+    #[test]
+    fn observers_matched_by_index() {
+        let left = json!({ "observers": [{ "index": 0, "floor_index": 0 }, { "index": 1, "floor_index": 0 }] });
+        let right = json!({ "observers": [{ "index": 2, "floor_index": 1 }, { "index": 0, "floor_index": 0 }] });
+        assert_eq!(
+            kinds(&left, &right),
+            vec![
+                (String::from("observers[index=1]"), String::from(KIND_REMOVED)),
+                (String::from("observers[index=2]"), String::from(KIND_ADDED)),
+            ]
+        );
+        // lists without indexes go by position
+        let left = json!({ "position": [1.0, 2.0] });
+        let right = json!({ "position": [1.0, 3.0, 4.0] });
+        assert_eq!(
+            kinds(&left, &right),
+            vec![
+                (String::from("position[1]"), String::from(KIND_CHANGED)),
+                (String::from("position[2]"), String::from(KIND_ADDED)),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_within_epsilon() {
+        let left = json!({ "position": [1.0, 2.0, 3.0], "fov": 90 });
+        let right = json!({ "position": [1.0 + 1e-12, 2.0, 3.0], "fov": 90.0 });
+        assert!(diff_work_values(&left, &right).identical);
+        let right = json!({ "position": [1.001, 2.0, 3.0], "fov": 90 });
+        assert_eq!(kinds(&left, &right), vec![(String::from("position[0]"), String::from(KIND_CHANGED))]);
+    }
+
+    #[test]
+    fn duplicate_indexes_are_reported() {
+        let left = json!({ "observers": [{ "index": 0, "x": 1 }, { "index": 0, "x": 2 }] });
+        let right = json!({ "observers": [{ "index": 0, "x": 1 }] });
+        let diff = diff_work_values(&left, &right);
+        assert_eq!(diff.differences.len(), 1);
+        assert_eq!(diff.differences[0].kind, KIND_DUPLICATE);
+        assert_eq!(diff.differences[0].left, Some(json!({ "index": 0, "x": 2 })));
+        assert!(diff.report.contains("! observers[index=0]: duplicate in left"));
+    }
+}
@@ -0,0 +1,426 @@
+use crate::command::work::{read_local_work, Work};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs;
+use tauri::InvokeError;
+
+// walk graph of a work, keyed by observer index
+pub struct NavGraph {
+    pub positions: BTreeMap<i64, Vec<f64>>,
+    pub floors: BTreeMap<i64, i64>,
+    pub accessible: BTreeMap<i64, Vec<i64>>,
+    pub visible: BTreeMap<i64, Vec<i64>>,
+    pub dangling: Vec<(i64, i64)>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FloorStat {
+    floor_index: i64,
+    panorama_count: usize,
+    edge_count: usize,
+    component_count: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GraphReport {
+    panorama_count: usize,
+    edge_count: usize,
+    one_way_edges: Vec<(i64, i64)>,
+    dangling_edges: Vec<(i64, i64)>,
+    components: Vec<Vec<i64>>,
+    isolated: Vec<i64>,
+    unreachable: Vec<i64>,
+    floors: Vec<FloorStat>,
+}
+
+impl NavGraph {
+    pub fn from_work(work: &Work) -> NavGraph {
+        let mut graph = NavGraph {
+            positions: BTreeMap::new(),
+            floors: BTreeMap::new(),
+            accessible: BTreeMap::new(),
+            visible: BTreeMap::new(),
+            dangling: Vec::new(),
+        };
+        for observer in work.observers.iter() {
+            graph.floors.insert(observer.index, observer.floor_index);
+            graph.positions.insert(observer.index, observer.position.clone());
+        }
+        for observer in work.observers.iter() {
+            let mut accessible: Vec<i64> = Vec::new();
+            for node in observer.accessible_nodes.iter() {
+                if !graph.floors.contains_key(node) {
+                    graph.dangling.push((observer.index, *node));
+                } else if *node != observer.index && !accessible.contains(node) {
+                    accessible.push(*node);
+                }
+            }
+            let visible: Vec<i64> = observer
+                .visible_nodes
+                .iter()
+                .filter(|node| graph.floors.contains_key(node) && **node != observer.index)
+                .cloned()
+                .collect();
+            graph.accessible.insert(observer.index, accessible);
+            graph.visible.insert(observer.index, visible);
+        }
+        graph
+    }
+
+    pub fn neighbors(&self, index: i64) -> &[i64] {
+        match self.accessible.get(&index) {
+            Some(list) => list,
+            None => &[],
+        }
+    }
+
+    pub fn edges(&self) -> Vec<(i64, i64)> {
+        let mut edges: Vec<(i64, i64)> = Vec::new();
+        for (from, list) in self.accessible.iter() {
+            for to in list.iter() {
+                edges.push((*from, *to));
+            }
+        }
+        edges
+    }
+
+    fn has_edge(&self, from: i64, to: i64) -> bool {
+        self.neighbors(from).contains(&to)
+    }
+
+    // connected components ignoring edge direction
+    pub fn components(&self, filter: &dyn Fn(i64) -> bool) -> Vec<Vec<i64>> {
+        let mut undirected: BTreeMap<i64, BTreeSet<i64>> = BTreeMap::new();
+        for (from, to) in self.edges() {
+            if filter(from) && filter(to) {
+                undirected.entry(from).or_default().insert(to);
+                undirected.entry(to).or_default().insert(from);
+            }
+        }
+        let mut seen: BTreeSet<i64> = BTreeSet::new();
+        let mut components: Vec<Vec<i64>> = Vec::new();
+        for index in self.floors.keys().filter(|i| filter(**i)) {
+            if seen.contains(index) {
+                continue;
+            }
+            let mut component: Vec<i64> = Vec::new();
+            let mut queue: VecDeque<i64> = VecDeque::from(vec![*index]);
+            seen.insert(*index);
+            while let Some(current) = queue.pop_front() {
+                component.push(current);
+                if let Some(list) = undirected.get(&current) {
+                    for next in list.iter() {
+                        if seen.insert(*next) {
+                            queue.push_back(*next);
+                        }
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components.sort_by(|a, b| b.len().cmp(&a.len()));
+        components
+    }
+
+    // panoramas that can be walked to from `start` following edge direction
+    pub fn reachable_from(&self, start: i64) -> BTreeSet<i64> {
+        let mut seen: BTreeSet<i64> = BTreeSet::new();
+        if !self.floors.contains_key(&start) {
+            return seen;
+        }
+        let mut queue: VecDeque<i64> = VecDeque::from(vec![start]);
+        seen.insert(start);
+        while let Some(current) = queue.pop_front() {
+            for next in self.neighbors(current) {
+                if seen.insert(*next) {
+                    queue.push_back(*next);
+                }
+            }
+        }
+        seen
+    }
+
+    // walking distance between two neighbouring panoramas
+    pub fn distance(&self, from: i64, to: i64) -> f64 {
+        match (self.positions.get(&from), self.positions.get(&to)) {
+            (Some(a), Some(b)) => a
+                .iter()
+                .zip(b.iter())
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f64>()
+                .sqrt(),
+            _ => 1.0,
+        }
+    }
+
+    // dijkstra over accessible edges, returns distances and predecessors
+    fn shortest_tree(&self, start: i64) -> (BTreeMap<i64, f64>, BTreeMap<i64, i64>) {
+        let mut dist: BTreeMap<i64, f64> = BTreeMap::new();
+        let mut prev: BTreeMap<i64, i64> = BTreeMap::new();
+        let mut done: BTreeSet<i64> = BTreeSet::new();
+        dist.insert(start, 0.0);
+        loop {
+            let current = dist
+                .iter()
+                .filter(|(index, _)| !done.contains(index))
+                .min_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .map(|(index, d)| (*index, *d));
+            let (current, current_dist) = match current {
+                Some(item) => item,
+                None => break,
+            };
+            done.insert(current);
+            for next in self.neighbors(current) {
+                let d = current_dist + self.distance(current, *next);
+                if dist.get(next).map_or(true, |old| d < *old) {
+                    dist.insert(*next, d);
+                    prev.insert(*next, current);
+                }
+            }
+        }
+        (dist, prev)
+    }
+
+    pub fn shortest_path(&self, from: i64, to: i64) -> Option<(Vec<i64>, f64)> {
+        let (dist, prev) = self.shortest_tree(from);
+        let total = *dist.get(&to)?;
+        let mut path: Vec<i64> = vec![to];
+        let mut current = to;
+        while current != from {
+            current = *prev.get(&current)?;
+            path.push(current);
+        }
+        path.reverse();
+        Some((path, total))
+    }
+
+    // greedy tour: keep walking to the closest panorama not visited yet
+    pub fn tour(&self, start: i64) -> (Vec<i64>, f64, Vec<i64>) {
+        let mut path: Vec<i64> = vec![start];
+        let mut total = 0.0;
+        let mut visited: BTreeSet<i64> = BTreeSet::new();
+        visited.insert(start);
+        let mut current = start;
+        loop {
+            let (dist, _) = self.shortest_tree(current);
+            let next = dist
+                .iter()
+                .filter(|(index, _)| !visited.contains(index))
+                .min_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .map(|(index, _)| *index);
+            let next = match next {
+                Some(next) => next,
+                None => break,
+            };
+            let (segment, d) = self.shortest_path(current, next).unwrap();
+            for index in segment.iter().skip(1) {
+                visited.insert(*index);
+                path.push(*index);
+            }
+            total = total + d;
+            current = next;
+        }
+        let unreachable: Vec<i64> = self
+            .floors
+            .keys()
+            .filter(|index| !visited.contains(index))
+            .cloned()
+            .collect();
+        (path, total, unreachable)
+    }
+
+    pub fn report(&self, start: i64) -> GraphReport {
+        let edges = self.edges();
+        let reachable = self.reachable_from(start);
+        let mut floors: Vec<FloorStat> = Vec::new();
+        let floor_set: BTreeSet<i64> = self.floors.values().cloned().collect();
+        for floor_index in floor_set.iter() {
+            let on_floor = |index: i64| self.floors.get(&index) == Some(floor_index);
+            floors.push(FloorStat {
+                floor_index: *floor_index,
+                panorama_count: self.floors.keys().filter(|i| on_floor(**i)).count(),
+                edge_count: edges.iter().filter(|(a, b)| on_floor(*a) && on_floor(*b)).count(),
+                component_count: self.components(&on_floor).len(),
+            });
+        }
+        GraphReport {
+            panorama_count: self.floors.len(),
+            edge_count: edges.len(),
+            one_way_edges: edges
+                .iter()
+                .filter(|(a, b)| !self.has_edge(*b, *a))
+                .cloned()
+                .collect(),
+            dangling_edges: self.dangling.clone(),
+            components: self.components(&|_| true),
+            isolated: self
+                .floors
+                .keys()
+                .filter(|i| {
+                    self.neighbors(**i).is_empty() && !edges.iter().any(|(_, to)| to == *i)
+                })
+                .cloned()
+                .collect(),
+            unreachable: self
+                .floors
+                .keys()
+                .filter(|i| !reachable.contains(i))
+                .cloned()
+                .collect(),
+            floors: floors,
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph work {\n    node [shape=circle];\n");
+        let floor_set: BTreeSet<i64> = self.floors.values().cloned().collect();
+        for floor_index in floor_set.iter() {
+            dot.push_str(&format!(
+                "    subgraph cluster_floor_{} {{\n        label=\"floor {}\";\n",
+                floor_index, floor_index
+            ));
+            for (index, _) in self.floors.iter().filter(|(_, f)| *f == floor_index) {
+                dot.push_str(&format!("        {};\n", index));
+            }
+            dot.push_str("    }\n");
+        }
+        for (from, to) in self.edges() {
+            // draw mutual edges once
+            if self.has_edge(to, from) {
+                if from < to {
+                    dot.push_str(&format!("    {} -> {} [dir=both];\n", from, to));
+                }
+            } else {
+                dot.push_str(&format!("    {} -> {} [style=dashed];\n", from, to));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let nodes: Vec<serde_json::Value> = self
+            .floors
+            .iter()
+            .map(|(index, floor_index)| {
+                serde_json::json!({
+                    "index": index,
+                    "floor_index": floor_index,
+                    "accessible_nodes": self.accessible.get(index),
+                    "visible_nodes": self.visible.get(index),
+                })
+            })
+            .collect();
+        serde_json::json!({ "nodes": nodes })
+    }
+}
+
+pub fn initial_pano(work: &Work) -> i64 {
+    work.initial
+        .pano_index
+        .or(work.initial.pano)
+        .or(work.observers.first().map(|o| o.index))
+        .unwrap_or(0)
+}
+
+#[tauri::command]
+pub fn analyze_work_graph(dir: String) -> Result<GraphReport, InvokeError> {
+    match read_local_work(&dir) {
+        Ok(work) => Ok(NavGraph::from_work(&work).report(initial_pano(&work))),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[tauri::command]
+pub fn export_work_graph(dir: String, dest: String, format: String) -> Result<String, InvokeError> {
+    let work = read_local_work(&dir);
+    if let Err(err) = work {
+        return Err(InvokeError::from(err));
+    }
+    let graph = NavGraph::from_work(&work.unwrap());
+    let content = match format.as_str() {
+        "dot" => graph.to_dot(),
+        "json" => serde_json::to_string_pretty(&graph.to_json()).unwrap(),
+        _ => return Err(InvokeError::from(format!("unknown graph format `{}`", format))),
+    };
+    if let Err(err) = fs::write(&dest, content.as_bytes()) {
+        return Err(InvokeError::from(format!("write `{}` error {}", dest, err)));
+    }
+    Ok(String::from("ok"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NavGraph;
+    use std::collections::BTreeMap;
+
+    // panoramas given as (index, position, floor), edges as (from, to)
+    fn graph(nodes: &[(i64, [f64; 3], i64)], edges: &[(i64, i64)]) -> NavGraph {
+        let mut graph = NavGraph {
+            positions: BTreeMap::new(),
+            floors: BTreeMap::new(),
+            accessible: BTreeMap::new(),
+            visible: BTreeMap::new(),
+            dangling: Vec::new(),
+        };
+        for (index, position, floor_index) in nodes.iter() {
+            graph.positions.insert(*index, position.to_vec());
+            graph.floors.insert(*index, *floor_index);
+            graph.accessible.insert(*index, Vec::new());
+        }
+        for (from, to) in edges.iter() {
+            graph.accessible.get_mut(from).unwrap().push(*to);
+        }
+        graph
+    }
+
+    fn sample() -> NavGraph {
+        // 0 - 1 - 2 in an L, a long detour 0 - 3 - 2, 4 only one way into 0,
+        // 5 alone upstairs
+        graph(
+            &[
+                (0, [0.0, 0.0, 0.0], 0),
+                (1, [3.0, 0.0, 0.0], 0),
+                (2, [3.0, 4.0, 0.0], 0),
+                (3, [-5.0, 5.0, 0.0], 0),
+                (4, [0.0, -1.0, 0.0], 0),
+                (5, [0.0, 0.0, 3.0], 1),
+            ],
+            &[(0, 1), (1, 0), (1, 2), (2, 1), (0, 3), (3, 0), (3, 2), (2, 3), (4, 0)],
+        )
+    }
+
+    #[test]
+    fn shortest_path_takes_the_short_side() {
+        let graph = sample();
+        let (path, total) = graph.shortest_path(0, 2).unwrap();
+        assert_eq!(path, vec![0, 1, 2]);
+        assert!((total - 7.0).abs() < 1e-9);
+        assert_eq!(graph.shortest_path(0, 0), Some((vec![0], 0.0)));
+        // 4 -> 0 is one way and 5 has no edge
+        assert!(graph.shortest_path(0, 4).is_none());
+        assert!(graph.shortest_path(0, 5).is_none());
+        assert_eq!(graph.shortest_path(4, 1).unwrap().0, vec![4, 0, 1]);
+    }
+
+    #[test]
+    fn tour_visits_the_reachable_panoramas() {
+        let (path, total, unreachable) = sample().tour(0);
+        assert_eq!(path, vec![0, 1, 2, 3]);
+        assert!((total - (3.0 + 4.0 + 65f64.sqrt())).abs() < 1e-9);
+        assert_eq!(unreachable, vec![4, 5]);
+    }
+
+    #[test]
+    fn report_lists_graph_problems() {
+        let report = sample().report(0);
+        assert_eq!(report.panorama_count, 6);
+        assert_eq!(report.one_way_edges, vec![(4, 0)]);
+        assert_eq!(report.components, vec![vec![0, 1, 2, 3, 4], vec![5]]);
+        assert_eq!(report.isolated, vec![5]);
+        assert_eq!(report.unreachable, vec![4, 5]);
+        assert_eq!(report.floors.len(), 2);
+        assert_eq!(report.floors[0].component_count, 1);
+    }
+}
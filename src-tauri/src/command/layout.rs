@@ -0,0 +1,111 @@
+use crate::command::branding::BRANDED_DIR;
+use crate::command::tour::{read_walkthrough, write_tour_files, TOUR_FILES};
+use crate::command::viewer::viewer_files;
+use crate::command::work::{
+    read_local_work, read_selection, write_jsonp_from_origin, write_preview_files, write_raw_files,
+    write_selection, with_work_locked, Work, ORIGIN_DIR, PREVIEW_DIR,
+};
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+pub const LAYOUT_JSONP: &str = "jsonp";
+pub const LAYOUT_RAW: &str = "raw";
+
+fn check_origin(work: &Work, dir: &str) -> Result<(), String> {
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    for (_, name) in work.get_download_list().iter() {
+        if !origin_path.join(name).exists() {
+            return Err(format!("`{}` is missing in origin", name));
+        }
+    }
+    Ok(())
+}
+
+fn remove_preview(dir: &str) -> Result<(), String> {
+    let preview_path = Path::new(dir).join(PREVIEW_DIR);
+    if preview_path.exists() {
+        if let Err(err) = fs::remove_dir_all(&preview_path) {
+            return Err(format!("remove `{}` error: {}", preview_path.display(), err));
+        }
+    }
+    Ok(())
+}
+
+// the viewer files written next to the raw assets
+fn remove_raw_files(work: &Work, dir: &str) -> Result<(), String> {
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    _ = fs::remove_file(origin_path.join("work.js"));
+    _ = fs::remove_dir_all(origin_path.join(BRANDED_DIR));
+    for (name, _) in viewer_files(work, dir)? {
+        _ = fs::remove_file(origin_path.join(name));
+    }
+    for name in TOUR_FILES.iter() {
+        _ = fs::remove_file(origin_path.join(name));
+    }
+    Ok(())
+}
+
+// switch a downloaded work between the jsonp `preview/` and the raw `origin/` layout
+pub fn convert_layout(dir: &str, layout: &str) -> Result<Work, String> {
+    if layout != LAYOUT_JSONP && layout != LAYOUT_RAW {
+        return Err(format!("unknown layout `{}`", layout));
+    }
+    let mut selection = read_selection(dir.to_string());
+    let work = read_local_work(dir)?;
+    check_origin(&work, dir)?;
+    if layout == LAYOUT_RAW {
+        write_raw_files(&work, dir)?;
+        remove_preview(dir)?;
+    } else {
+        write_jsonp_from_origin(&work, dir)?;
+        write_preview_files(&work, dir)?;
+        remove_raw_files(&work, dir)?;
+    }
+    selection.raw = layout == LAYOUT_RAW;
+    write_selection(dir, &selection)?;
+    // the walkthrough follows the viewer
+    if let Some(walkthrough) = read_walkthrough(dir) {
+        write_tour_files(&work, dir, &walkthrough)?;
+    }
+    Ok(work)
+}
+
+// rebuild the viewer of a work from `origin/` and input.json, nothing is downloaded
+pub fn rebuild_preview(dir: &str) -> Result<Work, String> {
+    let work = read_local_work(dir)?;
+    check_origin(&work, dir)?;
+    if read_selection(dir.to_string()).raw {
+        write_raw_files(&work, dir)?;
+    } else {
+        // drop whatever is left, files of a damaged or older viewer included
+        remove_preview(dir)?;
+        write_jsonp_from_origin(&work, dir)?;
+        write_preview_files(&work, dir)?;
+    }
+    if let Some(walkthrough) = read_walkthrough(dir) {
+        write_tour_files(&work, dir, &walkthrough)?;
+    }
+    Ok(work)
+}
+
+#[tauri::command]
+pub async fn convert_work_layout(dir: String, layout: String) -> Result<Work, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || with_work_locked(&dir, || convert_layout(&dir, &layout))).await;
+    match result {
+        Ok(Ok(work)) => Ok(work),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn rebuild_work_preview(dir: String) -> Result<Work, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || with_work_locked(&dir, || rebuild_preview(&dir))).await;
+    match result {
+        Ok(Ok(work)) => Ok(work),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
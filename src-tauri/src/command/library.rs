@@ -0,0 +1,623 @@
+use crate::command::http::parse_html_title;
+use crate::command::work::{lock_work, read_local_work, unlock_work, ORIGIN_DIR};
+use crate::util::file;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, InvokeError};
+use tokio;
+
+const LIBRARY_FILE: &str = "library.json";
+// written next to input.json so a rescan knows where a work came from
+const SOURCE_FILE: &str = "source.json";
+// how deep a rescan looks below each root for input.json
+const SCAN_DEPTH: usize = 4;
+// the title is a nicety, a slow source page must not hang around
+const TITLE_TIMEOUT: Duration = Duration::from_secs(15);
+// what a download and the later commands write into a work directory,
+// the only things removed when a work is deleted from the catalog
+const WORK_FILES: [&str; 8] = [
+    "input.json",
+    "selection.json",
+    "source.json",
+    "manifest.json",
+    "branding.json",
+    "redactions.json",
+    "viewer.json",
+    "tour.json",
+];
+const WORK_DIRS: [&str; 5] = ["origin", "preview", "thumbnails", "snapshots", "redact_originals"];
+
+pub const STATUS_DOWNLOADING: &str = "downloading";
+pub const STATUS_COMPLETE: &str = "complete";
+pub const STATUS_FAILED: &str = "failed";
+pub const STATUS_INCOMPLETE: &str = "incomplete";
+pub const STATUS_MISSING: &str = "missing";
+
+lazy_static! {
+    // the download runner and the ui both update the catalog
+    static ref LIBRARY_LOCK: Arc<Mutex<()>> = Arc::new(Mutex::new(()));
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WorkSource {
+    pub url: Option<String>,
+    #[serde(default)]
+    pub title: String,
+    pub downloaded_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LibraryEntry {
+    pub id: u64,
+    pub name: String,
+    pub title: String,
+    pub source_url: Option<String>,
+    pub downloaded_at: Option<String>,
+    pub dir: String,
+    pub size: u64,
+    pub panorama_count: usize,
+    pub floor_count: usize,
+    pub status: String,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct LibraryFilter {
+    // matched against name, title, source url and directory
+    pub query: Option<String>,
+    pub status: Option<String>,
+    pub min_panoramas: Option<usize>,
+    pub max_panoramas: Option<usize>,
+}
+
+fn library_path(app: &AppHandle) -> Result<PathBuf, String> {
+    match app.path_resolver().app_data_dir() {
+        Some(dir) => Ok(dir.join(LIBRARY_FILE)),
+        None => Err(String::from("app data dir not found")),
+    }
+}
+
+pub fn read_library(app: &AppHandle) -> Result<Vec<LibraryEntry>, String> {
+    let path = library_path(app)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path);
+    if let Err(err) = content {
+        return Err(format!("read {} error: {}", LIBRARY_FILE, err));
+    }
+    match serde_json::from_str(&content.unwrap()) {
+        Ok(list) => Ok(list),
+        Err(err) => Err(format!("json_decode {} error: {}", LIBRARY_FILE, err)),
+    }
+}
+
+fn write_library(app: &AppHandle, list: &Vec<LibraryEntry>) -> Result<(), String> {
+    let path = library_path(app)?;
+    if let Err(err) = fs::create_dir_all(path.parent().unwrap()) {
+        return Err(err.to_string());
+    }
+    if let Err(err) = fs::write(&path, serde_json::to_string_pretty(list).unwrap().as_bytes()) {
+        return Err(format!("write {} error: {}", LIBRARY_FILE, err));
+    }
+    Ok(())
+}
+
+// read, change and write the catalog while holding the lock
+fn update_library<T>(
+    app: &AppHandle,
+    change: impl FnOnce(&mut Vec<LibraryEntry>) -> Result<T, String>,
+) -> Result<T, String> {
+    let _lock = LIBRARY_LOCK.lock().unwrap();
+    let mut list = read_library(app)?;
+    let result = change(&mut list)?;
+    write_library(app, &list)?;
+    Ok(result)
+}
+
+pub fn read_source(dir: &str) -> WorkSource {
+    match fs::read_to_string(Path::new(dir).join(SOURCE_FILE)) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => WorkSource::default(),
+    }
+}
+
+pub fn write_source(dir: &str, source: &WorkSource) -> Result<(), String> {
+    if let Err(err) = fs::write(
+        Path::new(dir).join(SOURCE_FILE),
+        serde_json::to_string_pretty(source).unwrap().as_bytes(),
+    ) {
+        return Err(format!("write {} error: {}", SOURCE_FILE, err));
+    }
+    Ok(())
+}
+
+fn same_dir(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => Path::new(a) == Path::new(b),
+    }
+}
+
+// what a work directory holds, read without the catalog lock
+struct DiskState {
+    found: bool,
+    // panoramas and floors, None when input.json can not be read
+    counts: Option<(usize, usize)>,
+    size: u64,
+    built: bool,
+}
+
+fn read_disk_state(dir: &str) -> DiskState {
+    let path = Path::new(dir);
+    if !path.join("input.json").is_file() {
+        return DiskState {
+            found: false,
+            counts: None,
+            size: 0,
+            built: false,
+        };
+    }
+    let counts = read_local_work(dir).ok().map(|work| {
+        let floors: HashSet<i64> = work.observers.iter().map(|o| o.floor_index).collect();
+        (work.panorama.list.len(), floors.len())
+    });
+    DiskState {
+        found: true,
+        counts,
+        size: file::list_files(dir).map(|list| list.iter().map(|f| f.1).sum()).unwrap_or(0),
+        // work.json is written last
+        built: path.join(ORIGIN_DIR).join("work.json").is_file(),
+    }
+}
+
+// size, counts and status as found on disk, `status` is kept while it still applies
+fn apply_disk_state(entry: &mut LibraryEntry, state: &DiskState) {
+    if !state.found {
+        entry.status = String::from(STATUS_MISSING);
+        return;
+    }
+    if let Some((panorama_count, floor_count)) = state.counts {
+        entry.panorama_count = panorama_count;
+        entry.floor_count = floor_count;
+    }
+    entry.size = state.size;
+    // a download or failure in progress keeps its status
+    if state.built && (entry.status == STATUS_INCOMPLETE || entry.status == STATUS_MISSING) {
+        entry.status = String::from(STATUS_COMPLETE);
+    } else if !state.built && (entry.status == STATUS_COMPLETE || entry.status == STATUS_MISSING) {
+        entry.status = String::from(STATUS_INCOMPLETE);
+    }
+}
+
+fn refresh_entry(entry: &mut LibraryEntry) {
+    let state = read_disk_state(&entry.dir);
+    apply_disk_state(entry, &state);
+}
+
+fn dir_name(dir: &str) -> String {
+    Path::new(dir)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or(dir.to_string())
+}
+
+fn new_entry(list: &Vec<LibraryEntry>, dir: &str, status: &str) -> LibraryEntry {
+    let source = read_source(dir);
+    let name = if source.title.is_empty() {
+        dir_name(dir)
+    } else {
+        source.title.clone()
+    };
+    LibraryEntry {
+        id: list.iter().map(|e| e.id).max().unwrap_or(0) + 1,
+        name,
+        title: source.title,
+        source_url: source.url,
+        downloaded_at: source.downloaded_at,
+        dir: dir.to_string(),
+        size: 0,
+        panorama_count: 0,
+        floor_count: 0,
+        status: status.to_string(),
+    }
+}
+
+// a work the user just queued, the title is filled in later by `fetch_source_title`
+pub fn register_download(
+    app: &AppHandle,
+    dir: &str,
+    url: Option<String>,
+    name: Option<String>,
+) -> Result<LibraryEntry, String> {
+    let source = WorkSource {
+        url,
+        title: String::new(),
+        downloaded_at: Some(chrono::Utc::now().to_rfc3339()),
+    };
+    write_source(dir, &source)?;
+    let state = read_disk_state(dir);
+    update_library(app, |list| {
+        list.retain(|e| !same_dir(&e.dir, dir));
+        let mut entry = new_entry(list, dir, STATUS_DOWNLOADING);
+        if let Some(name) = name.filter(|n| !n.is_empty()) {
+            entry.name = name;
+        }
+        apply_disk_state(&mut entry, &state);
+        list.push(entry.clone());
+        Ok(entry)
+    })
+}
+
+// title of the source page into source.json and the catalog, an entry
+// still named after its directory is renamed to it
+pub async fn fetch_source_title(app: AppHandle, dir: String) {
+    let mut source = read_source(&dir);
+    let url = match &source.url {
+        Some(url) if source.title.is_empty() => url.clone(),
+        _ => return,
+    };
+    let title = match tokio::time::timeout(TITLE_TIMEOUT, parse_html_title(url)).await {
+        Ok(title) => title.trim().to_string(),
+        Err(_) => return,
+    };
+    if title.is_empty() {
+        return;
+    }
+    source.title = title.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        write_source(&dir, &source)?;
+        update_library(&app, |list| {
+            for entry in list.iter_mut().filter(|e| same_dir(&e.dir, &dir)) {
+                if entry.name == dir_name(&entry.dir) {
+                    entry.name = title.clone();
+                }
+                entry.title = title.clone();
+            }
+            Ok(())
+        })
+    })
+    .await;
+    match result {
+        Ok(Err(err)) => println!("fetch_source_title error {}", err),
+        Err(err) => println!("fetch_source_title error {}", err),
+        Ok(Ok(_)) => {}
+    }
+}
+
+// called by the download runner once a work is done, works not in the catalog are ignored
+pub fn finish_download(app: &AppHandle, dir: &str, ok: bool) -> Result<(), String> {
+    let state = read_disk_state(dir);
+    update_library(app, |list| {
+        for entry in list.iter_mut().filter(|e| same_dir(&e.dir, dir)) {
+            entry.status = String::from(if ok { STATUS_COMPLETE } else { STATUS_FAILED });
+            apply_disk_state(entry, &state);
+        }
+        Ok(())
+    })
+}
+
+fn matches(entry: &LibraryEntry, filter: &LibraryFilter) -> bool {
+    if let Some(query) = filter.query.as_ref().filter(|q| !q.is_empty()) {
+        let query = query.to_lowercase();
+        let found = [
+            entry.name.as_str(),
+            entry.title.as_str(),
+            entry.source_url.as_deref().unwrap_or(""),
+            entry.dir.as_str(),
+        ]
+        .iter()
+        .any(|field| field.to_lowercase().contains(&query));
+        if !found {
+            return false;
+        }
+    }
+    if let Some(status) = &filter.status {
+        if &entry.status != status {
+            return false;
+        }
+    }
+    if filter.min_panoramas.map_or(false, |min| entry.panorama_count < min) {
+        return false;
+    }
+    if filter.max_panoramas.map_or(false, |max| entry.panorama_count > max) {
+        return false;
+    }
+    true
+}
+
+// newest downloads first
+pub fn list_library(app: &AppHandle, filter: &LibraryFilter) -> Result<Vec<LibraryEntry>, String> {
+    let mut list: Vec<LibraryEntry> = read_library(app)?
+        .into_iter()
+        .filter(|e| matches(e, filter))
+        .collect();
+    list.sort_by(|a, b| b.downloaded_at.cmp(&a.downloaded_at).then(b.id.cmp(&a.id)));
+    Ok(list)
+}
+
+fn find_entry(list: &mut Vec<LibraryEntry>, id: u64) -> Result<&mut LibraryEntry, String> {
+    match list.iter_mut().find(|e| e.id == id) {
+        Some(entry) => Ok(entry),
+        None => Err(format!("library work {} not found", id)),
+    }
+}
+
+pub fn rename_entry(app: &AppHandle, id: u64, name: &str) -> Result<LibraryEntry, String> {
+    if name.trim().is_empty() {
+        return Err(String::from("name is empty"));
+    }
+    update_library(app, |list| {
+        let entry = find_entry(list, id)?;
+        entry.name = name.trim().to_string();
+        Ok(entry.clone())
+    })
+}
+
+// a rename only works on the same drive, otherwise copy and remove the original
+fn move_dir(from: &str, to: &str) -> Result<(), String> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if let Err(err) = file::copy_dir(from, to) {
+        _ = fs::remove_dir_all(to);
+        return Err(format!("move `{}` to `{}` error: {}", from, to, err));
+    }
+    if let Err(err) = fs::remove_dir_all(from) {
+        return Err(format!("remove `{}` error: {}", from, err));
+    }
+    Ok(())
+}
+
+// with `move_files` the directory is moved, otherwise it was moved by hand already
+pub fn relocate_entry(app: &AppHandle, id: u64, dir: &str, move_files: bool) -> Result<LibraryEntry, String> {
+    update_library(app, |list| {
+        let entry = find_entry(list, id)?;
+        if move_files {
+            if Path::new(dir).exists() {
+                return Err(format!("`{}` already exists", dir));
+            }
+            // not while it is downloaded or synced
+            lock_work(&entry.dir)?;
+            let moved = move_dir(&entry.dir, dir);
+            unlock_work(&entry.dir);
+            moved?;
+        } else if !Path::new(dir).join("input.json").is_file() {
+            return Err(format!("`{}` is not a downloaded work", dir));
+        }
+        entry.dir = dir.to_string();
+        refresh_entry(entry);
+        Ok(entry.clone())
+    })
+}
+
+// the directory is user chosen, only what a work writes is removed and the
+// directory itself only once nothing else is left in it
+fn delete_work_files(dir: &str) -> Result<(), String> {
+    let path = Path::new(dir);
+    if !path.join("input.json").is_file() {
+        return Err(format!("`{}` is not a downloaded work, nothing deleted", dir));
+    }
+    for name in WORK_DIRS.iter() {
+        let sub = path.join(name);
+        if sub.is_dir() {
+            if let Err(err) = fs::remove_dir_all(&sub) {
+                return Err(format!("remove `{}` error: {}", sub.display(), err));
+            }
+        }
+    }
+    for name in WORK_FILES.iter() {
+        let file = path.join(name);
+        if file.is_file() {
+            if let Err(err) = fs::remove_file(&file) {
+                return Err(format!("remove `{}` error: {}", file.display(), err));
+            }
+        }
+    }
+    _ = fs::remove_dir(path);
+    Ok(())
+}
+
+pub fn remove_entry(app: &AppHandle, id: u64, delete_files: bool) -> Result<(), String> {
+    update_library(app, |list| {
+        let dir = find_entry(list, id)?.dir.clone();
+        if delete_files && Path::new(&dir).exists() {
+            lock_work(&dir)?;
+            let deleted = delete_work_files(&dir);
+            unlock_work(&dir);
+            deleted?;
+        }
+        list.retain(|e| e.id != id);
+        Ok(())
+    })
+}
+
+fn find_works(dir: &Path, depth: usize, found: &mut Vec<String>) {
+    if dir.join("input.json").is_file() {
+        found.push(dir.to_string_lossy().to_string());
+        return;
+    }
+    if depth == 0 {
+        return;
+    }
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                find_works(&entry.path(), depth - 1, found);
+            }
+        }
+    }
+}
+
+// add every work found below `roots` and refresh the entries already known
+pub fn rescan_library(app: &AppHandle, roots: &Vec<String>) -> Result<Vec<LibraryEntry>, String> {
+    let mut found: Vec<String> = Vec::new();
+    for root in roots.iter() {
+        find_works(Path::new(root), SCAN_DEPTH, &mut found);
+    }
+    update_library(app, |list| {
+        for entry in list.iter_mut() {
+            refresh_entry(entry);
+        }
+        for dir in found.iter() {
+            if list.iter().any(|e| same_dir(&e.dir, dir)) {
+                continue;
+            }
+            let mut entry = new_entry(list, dir, STATUS_INCOMPLETE);
+            refresh_entry(&mut entry);
+            list.push(entry);
+        }
+        Ok(list.clone())
+    })
+}
+
+#[tauri::command]
+pub fn list_library_works(app: AppHandle, filter: Option<LibraryFilter>) -> Result<Vec<LibraryEntry>, InvokeError> {
+    match list_library(&app, &filter.unwrap_or_default()) {
+        Ok(list) => Ok(list),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[tauri::command]
+pub fn rename_library_work(app: AppHandle, id: u64, name: String) -> Result<LibraryEntry, InvokeError> {
+    match rename_entry(&app, id, &name) {
+        Ok(entry) => Ok(entry),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[tauri::command]
+pub async fn relocate_library_work(
+    app: AppHandle,
+    id: u64,
+    dir: String,
+    move_files: Option<bool>,
+) -> Result<LibraryEntry, InvokeError> {
+    let move_files = move_files.unwrap_or(false);
+    let result = tokio::task::spawn_blocking(move || relocate_entry(&app, id, &dir, move_files)).await;
+    match result {
+        Ok(Ok(entry)) => Ok(entry),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn remove_library_work(app: AppHandle, id: u64, delete_files: Option<bool>) -> Result<String, InvokeError> {
+    let delete_files = delete_files.unwrap_or(false);
+    let result = tokio::task::spawn_blocking(move || remove_entry(&app, id, delete_files)).await;
+    match result {
+        Ok(Ok(_)) => Ok(String::from("ok")),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn rescan_library_works(app: AppHandle, roots: Vec<String>) -> Result<Vec<LibraryEntry>, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || rescan_library(&app, &roots)).await;
+    match result {
+        Ok(Ok(list)) => Ok(list),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_disk_state, matches, DiskState, LibraryEntry, LibraryFilter, STATUS_COMPLETE, STATUS_DOWNLOADING,
+        STATUS_FAILED,
+        STATUS_INCOMPLETE, STATUS_MISSING,
+    };
+
+    fn entry(status: &str) -> LibraryEntry {
+        LibraryEntry {
+            id: 1,
+            name: String::from("Loft"),
+            title: String::from("Sunny Loft Apartment"),
+            source_url: Some(String::from("https://example.com/vr/abc123")),
+            downloaded_at: None,
+            dir: String::from("/data/works/loft"),
+            size: 0,
+            panorama_count: 0,
+            floor_count: 0,
+            status: status.to_string(),
+        }
+    }
+
+    fn state(found: bool, built: bool) -> DiskState {
+        DiskState {
+            found,
+            counts: if found { Some((12, 2)) } else { None },
+            size: 1024,
+            built,
+        }
+    }
+
+    fn status_after(status: &str, state: &DiskState) -> String {
+        let mut entry = entry(status);
+        apply_disk_state(&mut entry, state);
+        entry.status
+    }
+
+    #[test]
+    fn disk_state_transitions() {
+        for status in [STATUS_COMPLETE, STATUS_INCOMPLETE, STATUS_FAILED] {
+            assert_eq!(status_after(status, &state(false, false)), STATUS_MISSING);
+        }
+        assert_eq!(status_after(STATUS_MISSING, &state(true, true)), STATUS_COMPLETE);
+        assert_eq!(status_after(STATUS_INCOMPLETE, &state(true, true)), STATUS_COMPLETE);
+        assert_eq!(status_after(STATUS_MISSING, &state(true, false)), STATUS_INCOMPLETE);
+        assert_eq!(status_after(STATUS_COMPLETE, &state(true, false)), STATUS_INCOMPLETE);
+        // a failure or a download in progress is left alone
+        assert_eq!(status_after(STATUS_FAILED, &state(true, true)), STATUS_FAILED);
+        assert_eq!(status_after(STATUS_DOWNLOADING, &state(true, false)), STATUS_DOWNLOADING);
+
+        let mut found = entry(STATUS_COMPLETE);
+        apply_disk_state(&mut found, &state(true, true));
+        assert_eq!((found.panorama_count, found.floor_count, found.size), (12, 2, 1024));
+        // an unreadable input.json keeps the counts
+        let mut unreadable = found.clone();
+        apply_disk_state(&mut unreadable, &DiskState { counts: None, ..state(true, true) });
+        assert_eq!(unreadable.panorama_count, 12);
+    }
+
+    #[test]
+    fn filter_fields() {
+        let mut loft = entry(STATUS_COMPLETE);
+        loft.panorama_count = 12;
+        let query = |q: &str| LibraryFilter {
+            query: Some(q.to_string()),
+            ..LibraryFilter::default()
+        };
+        assert!(matches(&loft, &LibraryFilter::default()));
+        // name, title, source url and dir, ignoring case
+        assert!(matches(&loft, &query("loft")));
+        assert!(matches(&loft, &query("SUNNY")));
+        assert!(matches(&loft, &query("abc123")));
+        assert!(matches(&loft, &query("/data/works")));
+        assert!(matches(&loft, &query("")));
+        assert!(!matches(&loft, &query("cabin")));
+
+        let status = |s: &str| LibraryFilter {
+            status: Some(s.to_string()),
+            ..LibraryFilter::default()
+        };
+        assert!(matches(&loft, &status(STATUS_COMPLETE)));
+        assert!(!matches(&loft, &status(STATUS_MISSING)));
+
+        let range = |min: Option<usize>, max: Option<usize>| LibraryFilter {
+            min_panoramas: min,
+            max_panoramas: max,
+            ..LibraryFilter::default()
+        };
+        assert!(matches(&loft, &range(Some(12), Some(12))));
+        assert!(!matches(&loft, &range(Some(13), None)));
+        assert!(!matches(&loft, &range(None, Some(11))));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::command::model::MergedObj;
+use crate::command::work::{
+    create_file_directory, read_local_work, write_jsonp_from_origin, write_preview_files, Quaternion,
+    Work, ORIGIN_DIR,
+};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+// a downloaded work and where it sits in the merged tour
+#[derive(Deserialize, Clone, Debug)]
+pub struct MergeSource {
+    dir: String,
+    translation: Option<Vec<f64>>,
+    // degrees around the vertical axis
+    rotation: Option<f64>,
+    floor_offset: Option<i64>,
+}
+
+fn with_prefix(prefix: &str, name: &str) -> String {
+    if name.is_empty() {
+        return String::new();
+    }
+    format!("{}/{}", prefix, name)
+}
+
+fn transform_point(point: &Vec<f64>, rotation: &Quaternion, translation: &Vec<f64>) -> Vec<f64> {
+    if point.len() < 3 {
+        return point.clone();
+    }
+    let rotated = rotation.rotate(point);
+    (0..3)
+        .map(|i| rotated[i] + translation.get(i).cloned().unwrap_or(0.0))
+        .collect()
+}
+
+fn copy_assets(work: &Work, src_dir: &str, dest_dir: &str, prefix: &str) -> Result<(), String> {
+    let src_path = Path::new(src_dir).join(ORIGIN_DIR);
+    let dest_path = Path::new(dest_dir).join(ORIGIN_DIR).join(prefix);
+    for (_, name) in work.get_download_list().iter() {
+        let dest = dest_path.join(name);
+        create_file_directory(dest.to_str().unwrap())?;
+        if let Err(err) = fs::copy(src_path.join(name), &dest) {
+            return Err(format!("copy `{}` of `{}` error: {}", name, src_dir, err));
+        }
+    }
+    Ok(())
+}
+
+// the observers and panoramas of `work` after those of `target`, placed in the
+// merged tour
+fn append_work(
+    target: &mut Work,
+    work: &Work,
+    prefix: &str,
+    rotation: &Quaternion,
+    translation: &Vec<f64>,
+    floor_offset: i64,
+) {
+    // indexes of a work are not always contiguous
+    let offset = target
+        .observers
+        .iter()
+        .map(|o| o.index)
+        .chain(target.panorama.list.iter().map(|p| p.index))
+        .max()
+        .map_or(0, |max| max + 1);
+    let remap = |nodes: &Vec<i64>| -> Vec<i64> { nodes.iter().map(|node| node + offset).collect() };
+    for observer in work.observers.iter() {
+        let mut observer = observer.clone();
+        observer.index = observer.index + offset;
+        observer.floor_index = observer.floor_index + floor_offset;
+        observer.accessible_nodes = remap(&observer.accessible_nodes);
+        observer.visible_nodes = remap(&observer.visible_nodes);
+        observer.position = transform_point(&observer.position, rotation, translation);
+        observer.standing_position =
+            transform_point(&observer.standing_position, rotation, translation);
+        observer.quaternion = rotation.multiply(&observer.quaternion);
+        target.observers.push(observer);
+    }
+    for item in work.panorama.list.iter() {
+        let mut item = item.clone();
+        item.index = item.index + offset;
+        item.right = with_prefix(prefix, &item.right);
+        item.left = with_prefix(prefix, &item.left);
+        item.front = with_prefix(prefix, &item.front);
+        item.back = with_prefix(prefix, &item.back);
+        item.up = with_prefix(prefix, &item.up);
+        item.down = with_prefix(prefix, &item.down);
+        target.panorama.list.push(item);
+    }
+    target.panorama.count = target.panorama.list.len() as i64;
+}
+
+// the merged work keeps the initial view and pictures of the first source,
+// every work's assets are moved under `origin/w<n>/` and their OBJ models are
+// combined into one, a work without OBJ keeps the model of the first source
+pub fn merge(sources: &Vec<MergeSource>, dest_dir: &str) -> Result<Work, String> {
+    if sources.is_empty() {
+        return Err(String::from("no work to merge"));
+    }
+    let mut merged: Option<Work> = None;
+    let mut merged_obj = MergedObj::default();
+    for (n, source) in sources.iter().enumerate() {
+        let work = read_local_work(&source.dir)?;
+        let prefix = format!("w{}", n);
+        copy_assets(&work, &source.dir, dest_dir, &prefix)?;
+
+        let rotation = Quaternion::from_axis_angle(
+            [0.0, 1.0, 0.0],
+            source.rotation.unwrap_or(0.0).to_radians(),
+        );
+        let translation = source.translation.clone().unwrap_or(vec![0.0, 0.0, 0.0]);
+        let floor_offset = source.floor_offset.unwrap_or(0);
+        let origin_path = Path::new(&source.dir).join(ORIGIN_DIR);
+        if let Err(err) = merged_obj.append(&work, &origin_path, &prefix, &rotation, &translation) {
+            println!("model of `{}` is not merged: {}", source.dir, err);
+        }
+
+        let target = match merged.as_mut() {
+            Some(target) => target,
+            None => {
+                let mut first = work.clone();
+                first.base_url = String::new();
+                first.observers = Vec::new();
+                first.panorama.list = Vec::new();
+                first.model.file_url = with_prefix(&prefix, &work.model.file_url);
+                first.model.material_base_url = with_prefix(&prefix, &work.model.material_base_url);
+                if work.model.material_base_url.is_empty() {
+                    first.model.material_base_url = format!("{}/", prefix);
+                }
+                // the pictures are copied as `w0/picture.jpg`, move them back
+                for name in ["picture.jpg", "title_picture.jpg"] {
+                    let from = Path::new(dest_dir).join(ORIGIN_DIR).join(&prefix).join(name);
+                    if from.exists() {
+                        if let Err(err) = fs::copy(&from, Path::new(dest_dir).join(ORIGIN_DIR).join(name)) {
+                            return Err(format!("copy `{}` error: {}", name, err));
+                        }
+                    }
+                }
+                merged = Some(first);
+                merged.as_mut().unwrap()
+            }
+        };
+
+        append_work(target, &work, &prefix, &rotation, &translation, floor_offset);
+    }
+
+    let mut work = merged.unwrap();
+    if !merged_obj.is_empty() {
+        work.model = merged_obj.write(&Path::new(dest_dir).join(ORIGIN_DIR))?;
+    }
+    if let Err(err) = fs::write(
+        Path::new(dest_dir).join("input.json"),
+        serde_json::to_string(&work).unwrap().as_bytes(),
+    ) {
+        return Err(format!("write work input.json error:{}", err));
+    }
+    write_jsonp_from_origin(&work, dest_dir)?;
+    write_preview_files(&work, dest_dir)?;
+    Ok(work)
+}
+
+#[tauri::command]
+pub async fn merge_works(sources: Vec<MergeSource>, dest_dir: String) -> Result<Work, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || merge(&sources, &dest_dir)).await;
+    match result {
+        Ok(Ok(work)) => Ok(work),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::append_work;
+    use crate::command::work::tests::sample;
+    use crate::command::work::Quaternion;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn append_after_the_highest_index() {
+        let mut target = sample();
+        // a work with a gap in its indexes
+        target.observers.retain(|o| o.index != 1);
+        target.panorama.list.retain(|p| p.index != 3);
+        let mut work = sample().subset(&vec![0, 1]);
+        work.observers[0].accessible_nodes = vec![1];
+        work.observers[1].visible_nodes = vec![0];
+        let rotation = Quaternion::from_axis_angle([0.0, 1.0, 0.0], PI / 2.0);
+        append_work(&mut target, &work, "w1", &rotation, &vec![10.0, 0.0, 0.0], 2);
+
+        let indexes: Vec<i64> = target.observers.iter().map(|o| o.index).collect();
+        assert_eq!(indexes, vec![0, 2, 3, 4, 5]);
+        assert_eq!(target.observers[3].accessible_nodes, vec![5]);
+        assert_eq!(target.observers[4].visible_nodes, vec![4]);
+        assert_eq!(target.observers[4].floor_index, 2);
+        // (1, 0, 0) turned a quarter around y, then moved
+        let position = &target.observers[4].position;
+        assert!((position[0] - 10.0).abs() < 1e-9 && (position[2] + 1.0).abs() < 1e-9);
+
+        assert_eq!(target.panorama.count, 5);
+        let last = target.panorama.list.last().unwrap();
+        assert_eq!(last.index, 5);
+        assert_eq!(last.front, "w1/images/1/front.jpg");
+    }
+}
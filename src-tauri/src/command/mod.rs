@@ -1,4 +1,27 @@
 pub mod base;
 pub mod file;
 pub mod work;
-pub mod http;
\ No newline at end of file
+pub mod http;
+pub mod panorama;
+pub mod builder;
+pub mod model;
+pub mod pose;
+pub mod graph;
+pub mod tour;
+pub mod split;
+pub mod merge;
+pub mod rehost;
+pub mod publish;
+pub mod sftp;
+pub mod s3;
+pub mod server;
+pub mod layout;
+pub mod single;
+pub mod transcode;
+pub mod thumbnail;
+pub mod branding;
+pub mod redact;
+pub mod viewer;
+pub mod library;
+pub mod sync;
+pub mod diff;
\ No newline at end of file
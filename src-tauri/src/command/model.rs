@@ -0,0 +1,866 @@
+use crate::command::work::{read_local_work, Model, Quaternion, Work, ORIGIN_DIR};
+use crate::util::gltf::{GltfDocument, GltfImage, GltfMaterial, GltfNode, GltfPrimitive};
+use crate::util::lzma;
+use crate::util::url::strip_query;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::InvokeError;
+use tokio;
+
+const SRC_MODEL_DIR: &str = "src_model";
+const MATERIAL_TEXTURE_DIR: &str = "material_texture";
+// combined OBJ/MTL of a merged work, inside `origin/`
+const MERGED_MODEL_DIR: &str = "merged_model";
+// five.js takes at most this many LZMA streams in an `at3d` file
+const MAX_AT3D_STREAMS: usize = 10;
+
+struct ObjMaterial {
+    base_color: [f32; 4],
+    texture: Option<PathBuf>,
+}
+
+struct ObjGroup {
+    material: String,
+    // (position, uv, normal) indices of every triangle corner
+    corners: Vec<(usize, Option<usize>, Option<usize>)>,
+}
+
+#[derive(Default)]
+struct ObjMesh {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    groups: Vec<ObjGroup>,
+    material_libs: Vec<String>,
+}
+
+fn parse_floats<const N: usize>(parts: &[&str]) -> Option<[f32; N]> {
+    let mut out = [0f32; N];
+    for i in 0..N {
+        out[i] = parts.get(i)?.parse::<f32>().ok()?;
+    }
+    Some(out)
+}
+
+// obj indices are 1-based, negative values count from the end
+fn resolve_index(value: &str, len: usize) -> Option<usize> {
+    let index = value.parse::<i64>().ok()?;
+    if index > 0 && index as usize <= len {
+        return Some(index as usize - 1);
+    }
+    if index < 0 && (-index) as usize <= len {
+        return Some((len as i64 + index) as usize);
+    }
+    None
+}
+
+fn parse_obj(content: &str) -> Result<ObjMesh, String> {
+    let mut mesh = ObjMesh::default();
+    let mut current = String::new();
+    for (line_no, line) in content.lines().enumerate() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.is_empty() {
+            continue;
+        }
+        match parts[0] {
+            "v" => match parse_floats::<3>(&parts[1..]) {
+                Some(v) => mesh.positions.push(v),
+                None => return Err(format!("invalid vertex at line {}", line_no + 1)),
+            },
+            "vt" => match parse_floats::<2>(&parts[1..]) {
+                // obj uv origin is bottom-left, glTF is top-left
+                Some(vt) => mesh.uvs.push([vt[0], 1.0 - vt[1]]),
+                None => return Err(format!("invalid uv at line {}", line_no + 1)),
+            },
+            "vn" => match parse_floats::<3>(&parts[1..]) {
+                Some(vn) => mesh.normals.push(vn),
+                None => return Err(format!("invalid normal at line {}", line_no + 1)),
+            },
+            "mtllib" => mesh.material_libs.push(parts[1..].join(" ")),
+            "usemtl" => current = parts[1..].join(" "),
+            "f" => {
+                let mut corners: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
+                for item in parts[1..].iter() {
+                    let refs: Vec<&str> = item.split('/').collect();
+                    let position = resolve_index(refs[0], mesh.positions.len());
+                    if position.is_none() {
+                        return Err(format!("invalid face at line {}", line_no + 1));
+                    }
+                    let uv = refs.get(1).and_then(|v| resolve_index(v, mesh.uvs.len()));
+                    let normal = refs.get(2).and_then(|v| resolve_index(v, mesh.normals.len()));
+                    corners.push((position.unwrap(), uv, normal));
+                }
+                if mesh.groups.last().map_or(true, |g| g.material != current) {
+                    mesh.groups.push(ObjGroup {
+                        material: current.clone(),
+                        corners: Vec::new(),
+                    });
+                }
+                let group = mesh.groups.last_mut().unwrap();
+                // triangulate polygons as a fan
+                for i in 1..corners.len().saturating_sub(1) {
+                    group.corners.push(corners[0]);
+                    group.corners.push(corners[i]);
+                    group.corners.push(corners[i + 1]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+fn parse_mtl(content: &str, dir: &Path) -> HashMap<String, ObjMaterial> {
+    let mut materials: HashMap<String, ObjMaterial> = HashMap::new();
+    let mut current = String::new();
+    for line in content.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.is_empty() {
+            continue;
+        }
+        match parts[0] {
+            "newmtl" => {
+                current = parts[1..].join(" ");
+                materials.insert(
+                    current.clone(),
+                    ObjMaterial {
+                        base_color: [1.0, 1.0, 1.0, 1.0],
+                        texture: None,
+                    },
+                );
+            }
+            "Kd" => {
+                if let (Some(material), Some(kd)) = (materials.get_mut(&current), parse_floats::<3>(&parts[1..])) {
+                    material.base_color[0] = kd[0];
+                    material.base_color[1] = kd[1];
+                    material.base_color[2] = kd[2];
+                }
+            }
+            "d" => {
+                if let (Some(material), Some(d)) = (materials.get_mut(&current), parse_floats::<1>(&parts[1..])) {
+                    material.base_color[3] = d[0];
+                }
+            }
+            "map_Kd" => {
+                if let Some(material) = materials.get_mut(&current) {
+                    // options such as `-s 1 1 1` come before the file name
+                    material.texture = Some(dir.join(parts[parts.len() - 1].replace('\\', "/")));
+                }
+            }
+            _ => {}
+        }
+    }
+    materials
+}
+
+fn find_file_by_ext(dir: &Path, ext: &str) -> Option<PathBuf> {
+    let entry = fs::read_dir(dir).ok()?;
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for item in entry.flatten() {
+        let path = item.path();
+        if path.is_dir() {
+            dirs.push(path);
+        } else if path.extension().map_or(false, |e| e.to_string_lossy().to_lowercase() == ext) {
+            return Some(path);
+        }
+    }
+    dirs.iter().find_map(|d| find_file_by_ext(d, ext))
+}
+
+// only OBJ/MTL is converted, either downloaded as the model itself or from the
+// extracted `src_model` bundle which carries the OBJ/MTL export of the host,
+// the host's other model types can not be read
+pub fn find_obj_file(work: &Work, origin_path: &Path) -> Result<PathBuf, String> {
+    let bundle_path = origin_path.join(SRC_MODEL_DIR);
+    if work.model.file_url.to_lowercase().ends_with(".obj") {
+        let path = origin_path.join(&work.model.file_url);
+        if path.exists() {
+            return Ok(path);
+        }
+    } else if !bundle_path.is_dir() {
+        return Err(format!(
+            "unsupported model_type {} (`{}`), only OBJ/MTL, pbm and at3d models can be exported",
+            work.model.model_type, work.model.file_url
+        ));
+    }
+    match find_file_by_ext(&bundle_path, "obj") {
+        Some(path) => Ok(path),
+        None => Err(format!(
+            "no OBJ found for model `{}` in `{}`",
+            work.model.file_url, SRC_MODEL_DIR
+        )),
+    }
+}
+
+fn find_file_by_name(dir: &Path, file_name: &std::ffi::OsStr) -> Option<PathBuf> {
+    let candidate = dir.join(file_name);
+    if candidate.is_file() {
+        return Some(candidate);
+    }
+    for item in fs::read_dir(dir).ok()?.flatten() {
+        if item.path().is_dir() {
+            if let Some(path) = find_file_by_name(&item.path(), file_name) {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+// the mtl may point to a path of the capture machine, so fall back to the
+// downloaded `material_textures` and the extracted `material_texture.zip`
+fn find_texture(path: &Path, work: &Work, origin_path: &Path) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.to_path_buf());
+    }
+    let file_name = path.file_name()?;
+    let material_path = origin_path.join(work.model.material_base_url.trim_start_matches('/'));
+    if material_path.join(file_name).is_file() {
+        return Some(material_path.join(file_name));
+    }
+    let texture_path = origin_path
+        .join(SRC_MODEL_DIR)
+        .join("material")
+        .join(MATERIAL_TEXTURE_DIR);
+    find_file_by_name(&texture_path, file_name)
+}
+
+fn image_mime_type(path: &Path) -> String {
+    match path.extension().map(|e| e.to_string_lossy().to_lowercase()) {
+        Some(ext) if ext == "png" => String::from("image/png"),
+        _ => String::from("image/jpeg"),
+    }
+}
+
+pub fn convert_obj_to_glb(obj_file: &Path, work: &Work, origin_path: &Path) -> Result<Vec<u8>, String> {
+    let content = fs::read_to_string(obj_file);
+    if let Err(err) = content {
+        return Err(format!("read `{}` error: {}", obj_file.display(), err));
+    }
+    let mesh = parse_obj(&content.unwrap())?;
+    let obj_dir = obj_file.parent().unwrap_or(Path::new("."));
+
+    let mut materials: HashMap<String, ObjMaterial> = HashMap::new();
+    for lib in mesh.material_libs.iter() {
+        if let Ok(content) = fs::read_to_string(obj_dir.join(lib)) {
+            let lib_dir = obj_dir.join(lib).parent().unwrap_or(obj_dir).to_path_buf();
+            materials.extend(parse_mtl(&content, &lib_dir));
+        }
+    }
+    mesh_to_glb(&mesh, &materials, work, origin_path, &obj_file.display().to_string())
+}
+
+fn mesh_to_glb(
+    mesh: &ObjMesh,
+    materials: &HashMap<String, ObjMaterial>,
+    work: &Work,
+    origin_path: &Path,
+    source: &str,
+) -> Result<Vec<u8>, String> {
+    let mut document = GltfDocument::new();
+    let mut material_index: HashMap<String, usize> = HashMap::new();
+    let mut image_index: HashMap<PathBuf, usize> = HashMap::new();
+    let mut primitives: Vec<GltfPrimitive> = Vec::new();
+    for group in mesh.groups.iter() {
+        if group.corners.is_empty() {
+            continue;
+        }
+        if !material_index.contains_key(&group.material) {
+            let mut texture: Option<usize> = None;
+            let mut base_color = [1.0, 1.0, 1.0, 1.0];
+            if let Some(material) = materials.get(&group.material) {
+                base_color = material.base_color;
+                if let Some(path) = material.texture.as_ref().and_then(|p| find_texture(p, work, origin_path)) {
+                    if !image_index.contains_key(&path) {
+                        if let Ok(data) = fs::read(&path) {
+                            document.images.push(GltfImage {
+                                mime_type: image_mime_type(&path),
+                                data: data,
+                            });
+                            image_index.insert(path.clone(), document.images.len() - 1);
+                        }
+                    }
+                    texture = image_index.get(&path).cloned();
+                }
+            }
+            // the texture carries the colour, Kd is usually a placeholder
+            if texture.is_some() {
+                base_color = [1.0, 1.0, 1.0, base_color[3]];
+            }
+            document.materials.push(GltfMaterial {
+                name: group.material.clone(),
+                base_color: base_color,
+                texture: texture,
+            });
+            material_index.insert(group.material.clone(), document.materials.len() - 1);
+        }
+
+        // glTF needs one index per vertex, so split on (position, uv, normal)
+        let mut primitive = GltfPrimitive {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices: Vec::new(),
+            material: material_index.get(&group.material).cloned(),
+        };
+        let has_uv = group.corners.iter().all(|c| c.1.is_some());
+        let has_normal = group.corners.iter().all(|c| c.2.is_some());
+        let mut vertex_index: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+        for corner in group.corners.iter() {
+            let index = match vertex_index.get(corner) {
+                Some(index) => *index,
+                None => {
+                    primitive.positions.push(mesh.positions[corner.0]);
+                    if has_uv {
+                        primitive.uvs.push(mesh.uvs[corner.1.unwrap()]);
+                    }
+                    if has_normal {
+                        primitive.normals.push(mesh.normals[corner.2.unwrap()]);
+                    }
+                    let index = primitive.positions.len() as u32 - 1;
+                    vertex_index.insert(*corner, index);
+                    index
+                }
+            };
+            primitive.indices.push(index);
+        }
+        primitives.push(primitive);
+    }
+    if primitives.is_empty() {
+        return Err(format!("no face found in `{}`", source));
+    }
+    document.meshes.push((String::from("model"), primitives));
+    document.nodes.push(GltfNode {
+        name: String::from("model"),
+        mesh: Some(0),
+        camera: None,
+        translation: None,
+        rotation: None,
+    });
+    Ok(document.to_glb())
+}
+
+// reader of the protobuf messages of the `pbm` and `at3d` models
+struct ProtoReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(data: &'a [u8]) -> ProtoReader<'a> {
+        ProtoReader { data, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = match self.data.get(self.pos) {
+                Some(byte) => *byte,
+                None => return Err(String::from("model message is truncated")),
+            };
+            self.pos = self.pos + 1;
+            value = value | (((byte & 0x7f) as u64) << shift);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(String::from("model message has an invalid varint"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() - self.pos < len {
+            return Err(String::from("model message is truncated"));
+        }
+        self.pos = self.pos + len;
+        Ok(&self.data[self.pos - len..self.pos])
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.varint()? as usize;
+        self.take(len)
+    }
+
+    // (field number, wire type) of the next field, None at the end
+    fn field(&mut self) -> Result<Option<(u64, u64)>, String> {
+        if self.pos >= self.data.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        Ok(Some((key >> 3, key & 7)))
+    }
+
+    fn skip(&mut self, wire: u64) -> Result<(), String> {
+        match wire {
+            0 => self.varint().map(|_| ()),
+            1 => self.take(8).map(|_| ()),
+            2 => self.bytes().map(|_| ()),
+            5 => self.take(4).map(|_| ()),
+            _ => Err(format!("model message has unknown wire type {}", wire)),
+        }
+    }
+
+    // repeated floats, packed or one per field
+    fn floats(&mut self, wire: u64, out: &mut Vec<f32>) -> Result<(), String> {
+        let data = if wire == 2 { self.bytes()? } else { self.take(4)? };
+        for value in data.chunks_exact(4) {
+            out.push(f32::from_le_bytes([value[0], value[1], value[2], value[3]]));
+        }
+        Ok(())
+    }
+
+    // repeated uint32, packed or one per field
+    fn uints(&mut self, wire: u64, out: &mut Vec<usize>) -> Result<(), String> {
+        if wire != 2 {
+            out.push(self.varint()? as usize);
+            return Ok(());
+        }
+        let mut packed = ProtoReader::new(self.bytes()?);
+        while packed.pos < packed.data.len() {
+            out.push(packed.varint()? as usize);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct ModelChunk {
+    name: String,
+    texture: String,
+    xyz: Vec<f32>,
+    uvs: Vec<f32>,
+    indices: Vec<usize>,
+}
+
+// `Model { repeated Chunk chunks = 1 }`, `Chunk { Vertices vertices = 1; Faces faces = 2;
+// string name = 3; string texture = 4 }`, `Vertices { repeated float xyz = 1; repeated
+// float uvs = 2 }` and `Faces { repeated uint32 indices = 1 }`
+fn decode_chunks(data: &[u8], chunks: &mut Vec<ModelChunk>) -> Result<(), String> {
+    let mut model = ProtoReader::new(data);
+    while let Some((field, wire)) = model.field()? {
+        if field != 1 || wire != 2 {
+            model.skip(wire)?;
+            continue;
+        }
+        let mut chunk = ModelChunk::default();
+        let mut reader = ProtoReader::new(model.bytes()?);
+        while let Some((field, wire)) = reader.field()? {
+            match (field, wire) {
+                (1, 2) => {
+                    let mut vertices = ProtoReader::new(reader.bytes()?);
+                    while let Some((field, wire)) = vertices.field()? {
+                        match field {
+                            1 => vertices.floats(wire, &mut chunk.xyz)?,
+                            2 => vertices.floats(wire, &mut chunk.uvs)?,
+                            _ => vertices.skip(wire)?,
+                        }
+                    }
+                }
+                (2, 2) => {
+                    let mut faces = ProtoReader::new(reader.bytes()?);
+                    while let Some((field, wire)) = faces.field()? {
+                        match field {
+                            1 => faces.uints(wire, &mut chunk.indices)?,
+                            _ => faces.skip(wire)?,
+                        }
+                    }
+                }
+                (3, 2) => chunk.name = String::from_utf8_lossy(reader.bytes()?).to_string(),
+                (4, 2) => chunk.texture = String::from_utf8_lossy(reader.bytes()?).to_string(),
+                _ => reader.skip(wire)?,
+            }
+        }
+        chunks.push(chunk);
+    }
+    Ok(())
+}
+
+// an `at3d` file is a count, the sizes and then that many LZMA streams, each
+// one a `pbm` model
+fn split_at3d(data: &[u8]) -> Result<Vec<&[u8]>, String> {
+    let read_u32 = |at: usize| -> Option<usize> {
+        let bytes = data.get(at..at + 4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    };
+    let count = read_u32(0).ok_or_else(|| String::from("at3d header is truncated"))?;
+    if count > MAX_AT3D_STREAMS {
+        return Err(format!("at3d has {} streams, at most {} are supported", count, MAX_AT3D_STREAMS));
+    }
+    let mut streams: Vec<&[u8]> = Vec::new();
+    let mut offset = 4 + 4 * count;
+    for i in 0..count {
+        let size = read_u32(4 + 4 * i).ok_or_else(|| String::from("at3d header is truncated"))?;
+        match data.get(offset..offset + size) {
+            Some(stream) => streams.push(stream),
+            None => return Err(String::from("at3d stream is truncated")),
+        }
+        offset = offset + size;
+    }
+    Ok(streams)
+}
+
+// five.js numbers chunks `chunk-<n>` and gives them the n-th material texture,
+// or the texture named by the chunk
+fn chunk_texture(chunk: &ModelChunk, model: &Model, origin_path: &Path) -> Option<PathBuf> {
+    let number = chunk
+        .name
+        .split("chunk")
+        .nth(1)
+        .map(|rest| rest.trim_start_matches('-'))
+        .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+        .and_then(|digits| digits.parse::<usize>().ok())
+        .unwrap_or(0);
+    let name = match model.material_textures.get(number) {
+        Some(texture) => texture.clone(),
+        None if !chunk.texture.is_empty() => chunk.texture.clone(),
+        None => return None,
+    };
+    Some(origin_path.join(format!("{}{}", model.material_base_url, name)))
+}
+
+// the chunks in one mesh, from the z-up frame of the host to the y-up one of
+// glTF (x, z, -y) as five.js does, uvs flipped like OBJ ones
+fn chunks_to_mesh(
+    chunks: &Vec<ModelChunk>,
+    model: &Model,
+    origin_path: &Path,
+) -> Result<(ObjMesh, HashMap<String, ObjMaterial>), String> {
+    let mut mesh = ObjMesh::default();
+    let mut materials: HashMap<String, ObjMaterial> = HashMap::new();
+    for (n, chunk) in chunks.iter().enumerate() {
+        let count = chunk.xyz.len() / 3;
+        let has_uv = chunk.uvs.len() == count * 2;
+        let (position_base, uv_base) = (mesh.positions.len(), mesh.uvs.len());
+        for v in chunk.xyz.chunks_exact(3) {
+            mesh.positions.push([v[0], v[2], -v[1]]);
+        }
+        if has_uv {
+            for uv in chunk.uvs.chunks_exact(2) {
+                mesh.uvs.push([uv[0], 1.0 - uv[1]]);
+            }
+        }
+        let material = format!("{}_{}", n, chunk.name);
+        let mut group = ObjGroup {
+            material: material.clone(),
+            corners: Vec::new(),
+        };
+        for index in chunk.indices.iter() {
+            if *index >= count {
+                return Err(format!("chunk `{}` has an invalid face index {}", chunk.name, index));
+            }
+            let uv = if has_uv { Some(uv_base + index) } else { None };
+            group.corners.push((position_base + index, uv, None));
+        }
+        // five.js only draws whole triangles
+        group.corners.truncate(group.corners.len() / 3 * 3);
+        mesh.groups.push(group);
+        materials.insert(
+            material,
+            ObjMaterial {
+                base_color: [1.0, 1.0, 1.0, 1.0],
+                texture: chunk_texture(chunk, model, origin_path),
+            },
+        );
+    }
+    Ok((mesh, materials))
+}
+
+// the host's model variants, told apart by the extension of `file_url` as five.js
+// does: `pbm` and `at3d` are converted, the `dome`/`domez` ones carry materials
+// five.js renders with its own shader and are refused, anything else goes
+// through its OBJ/MTL export
+pub fn convert_model_to_glb(work: &Work, origin_path: &Path) -> Result<Vec<u8>, String> {
+    let url = strip_query(&work.model.file_url);
+    let variant = Path::new(url)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match variant.as_str() {
+        "pbm" | "at3d" => {
+            let path = origin_path.join(url);
+            let data = fs::read(&path);
+            if let Err(err) = data {
+                return Err(format!("read `{}` error: {}", path.display(), err));
+            }
+            let data = data.unwrap();
+            let mut chunks: Vec<ModelChunk> = Vec::new();
+            if variant == "pbm" {
+                decode_chunks(&data, &mut chunks)?;
+            } else {
+                for stream in split_at3d(&data)? {
+                    decode_chunks(&lzma::decompress(stream)?, &mut chunks)?;
+                }
+            }
+            let (mesh, materials) = chunks_to_mesh(&chunks, &work.model, origin_path)?;
+            mesh_to_glb(&mesh, &materials, work, origin_path, url)
+        }
+        "dome" | "domez" => Err(format!(
+            "model_type {} (`{}`) is a dome model, only OBJ/MTL, pbm and at3d models can be exported",
+            work.model.model_type, work.model.file_url
+        )),
+        _ => convert_obj_to_glb(&find_obj_file(work, origin_path)?, work, origin_path),
+    }
+}
+
+// OBJ/MTL of several works placed in one model, the materials and textures of
+// every work are kept apart by its prefix
+#[derive(Default)]
+pub struct MergedObj {
+    obj: String,
+    mtl: String,
+    // texture file and its name under `merged_model/`
+    textures: Vec<(PathBuf, String)>,
+    positions: usize,
+    uvs: usize,
+    normals: usize,
+}
+
+fn format_floats(values: &[f64]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+// one `v/vt/vn` reference of a face, shifted behind the works already appended
+fn remap_ref(value: &str, len: usize, base: usize) -> Option<String> {
+    if value.is_empty() {
+        return Some(String::new());
+    }
+    resolve_index(value, len).map(|index| (index + base + 1).to_string())
+}
+
+impl MergedObj {
+    pub fn is_empty(&self) -> bool {
+        self.positions == 0
+    }
+
+    // append the OBJ of a work rotated then translated into the merged space
+    pub fn append(
+        &mut self,
+        work: &Work,
+        origin_path: &Path,
+        prefix: &str,
+        rotation: &Quaternion,
+        translation: &Vec<f64>,
+    ) -> Result<(), String> {
+        let obj_file = find_obj_file(work, origin_path)?;
+        let content = fs::read_to_string(&obj_file);
+        if let Err(err) = content {
+            return Err(format!("read `{}` error: {}", obj_file.display(), err));
+        }
+        let obj_dir = obj_file.parent().unwrap_or(Path::new("."));
+        let (mut positions, mut uvs, mut normals) = (0, 0, 0);
+        self.obj.push_str(&format!("o {}\n", prefix));
+        for (line_no, line) in content.unwrap().lines().enumerate() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.is_empty() {
+                continue;
+            }
+            let invalid = || format!("invalid line {} of `{}`", line_no + 1, obj_file.display());
+            match parts[0] {
+                "v" => {
+                    let v = parse_floats::<3>(&parts[1..]).ok_or_else(invalid)?;
+                    let point = rotation.rotate(&[v[0] as f64, v[1] as f64, v[2] as f64]);
+                    let moved: Vec<f64> = (0..3)
+                        .map(|i| point[i] + translation.get(i).cloned().unwrap_or(0.0))
+                        .collect();
+                    self.obj.push_str(&format!("v {}\n", format_floats(&moved)));
+                    positions = positions + 1;
+                }
+                "vt" => {
+                    parse_floats::<2>(&parts[1..]).ok_or_else(invalid)?;
+                    self.obj.push_str(&format!("{}\n", parts.join(" ")));
+                    uvs = uvs + 1;
+                }
+                "vn" => {
+                    let n = parse_floats::<3>(&parts[1..]).ok_or_else(invalid)?;
+                    let normal = rotation.rotate(&[n[0] as f64, n[1] as f64, n[2] as f64]);
+                    self.obj.push_str(&format!("vn {}\n", format_floats(&normal)));
+                    normals = normals + 1;
+                }
+                "f" => {
+                    let mut corners: Vec<String> = Vec::new();
+                    for item in parts[1..].iter() {
+                        let refs: Vec<&str> = item.split('/').collect();
+                        let mut remapped: Vec<String> = Vec::new();
+                        for (k, value) in refs.iter().enumerate() {
+                            let (len, base) = match k {
+                                0 => (positions, self.positions),
+                                1 => (uvs, self.uvs),
+                                _ => (normals, self.normals),
+                            };
+                            remapped.push(remap_ref(value, len, base).ok_or_else(invalid)?);
+                        }
+                        corners.push(remapped.join("/"));
+                    }
+                    self.obj.push_str(&format!("f {}\n", corners.join(" ")));
+                }
+                "usemtl" => self.obj.push_str(&format!("usemtl {}_{}\n", prefix, parts[1..].join(" "))),
+                "g" | "s" => self.obj.push_str(&format!("{}\n", parts.join(" "))),
+                "mtllib" => {
+                    let lib = obj_dir.join(parts[1..].join(" "));
+                    if let Ok(content) = fs::read_to_string(&lib) {
+                        let lib_dir = lib.parent().unwrap_or(obj_dir).to_path_buf();
+                        self.append_mtl(&content, &lib_dir, work, origin_path, prefix);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.positions = self.positions + positions;
+        self.uvs = self.uvs + uvs;
+        self.normals = self.normals + normals;
+        Ok(())
+    }
+
+    // only `map_Kd` is kept, the other maps point to files of the capture machine
+    fn append_mtl(&mut self, content: &str, dir: &Path, work: &Work, origin_path: &Path, prefix: &str) {
+        for line in content.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.is_empty() {
+                continue;
+            }
+            match parts[0] {
+                "newmtl" => self.mtl.push_str(&format!("\nnewmtl {}_{}\n", prefix, parts[1..].join(" "))),
+                "map_Kd" => {
+                    let path = dir.join(parts[parts.len() - 1].replace('\\', "/"));
+                    let texture = match find_texture(&path, work, origin_path) {
+                        Some(texture) => texture,
+                        None => continue,
+                    };
+                    let name = format!(
+                        "{}/{}",
+                        prefix,
+                        texture.file_name().unwrap_or_default().to_string_lossy()
+                    );
+                    if !self.textures.iter().any(|(_, n)| n == &name) {
+                        self.textures.push((texture, name.clone()));
+                    }
+                    self.mtl.push_str(&format!("map_Kd {}\n", name));
+                }
+                key if key.starts_with("map_") || key == "bump" || key == "disp" || key == "decal" => {}
+                _ => self.mtl.push_str(&format!("{}\n", parts.join(" "))),
+            }
+        }
+    }
+
+    // write `origin/merged_model/` and return the model the merged work loads
+    pub fn write(&self, origin_path: &Path) -> Result<Model, String> {
+        let model_path = origin_path.join(MERGED_MODEL_DIR);
+        if let Err(err) = fs::create_dir_all(&model_path) {
+            return Err(err.to_string());
+        }
+        let mut material_textures = vec![String::from("model.mtl")];
+        for (texture, name) in self.textures.iter() {
+            let dest = model_path.join(name);
+            if let Err(err) = fs::create_dir_all(dest.parent().unwrap_or(&model_path)) {
+                return Err(err.to_string());
+            }
+            if let Err(err) = fs::copy(texture, &dest) {
+                return Err(format!("copy `{}` error: {}", texture.display(), err));
+            }
+            material_textures.push(name.clone());
+        }
+        let obj = format!("mtllib model.mtl\n{}", self.obj);
+        for (name, content) in [("model.obj", &obj), ("model.mtl", &self.mtl)] {
+            if let Err(err) = fs::write(model_path.join(name), content.as_bytes()) {
+                return Err(format!("write `{}` error: {}", name, err));
+            }
+        }
+        Ok(Model {
+            file_url: format!("{}/model.obj", MERGED_MODEL_DIR),
+            material_base_url: format!("{}/", MERGED_MODEL_DIR),
+            material_textures,
+            model_type: 0,
+        })
+    }
+}
+
+pub fn export_work_model(dir: &str, dest: &str) -> Result<(), String> {
+    let work = read_local_work(dir)?;
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let glb = convert_model_to_glb(&work, &origin_path)?;
+    if let Err(err) = fs::write(dest, &glb) {
+        return Err(format!("write `{}` error: {}", dest, err));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn export_model_glb(dir: String, dest: String) -> Result<String, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || export_work_model(&dir, &dest)).await;
+    match result {
+        Ok(Ok(_)) => Ok(String::from("ok")),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunks_to_mesh, decode_chunks, lzma, split_at3d, ModelChunk};
+    use crate::command::work::Model;
+    use std::path::Path;
+
+    // one chunk `chunk-1` with texture `own.jpg`, vertices (1, 2, 3), (4, 5, 6),
+    // (7, 8, 9), uvs (0, 0.25), (1, 0), (0, 1) and the triangle 0 1 2
+    const PBM: [u8; 93] = [
+        10, 91, 10, 64, 10, 36, 0, 0, 128, 63, 0, 0, 0, 64, 0, 0, 64, 64, 0, 0, 128, 64, 0, 0, 160, 64,
+        0, 0, 192, 64, 0, 0, 224, 64, 0, 0, 0, 65, 0, 0, 16, 65, 18, 24, 0, 0, 0, 0, 0, 0, 128, 62, 0,
+        0, 128, 63, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 128, 63, 18, 5, 10, 3, 0, 1, 2, 26, 7, 99, 104, 117,
+        110, 107, 45, 49, 34, 7, 111, 119, 110, 46, 106, 112, 103,
+    ];
+
+    // the same model in an at3d container
+    const AT3D: [u8; 98] = [
+        1, 0, 0, 0, 90, 0, 0, 0, 93, 0, 0, 128, 0, 255, 255, 255, 255, 255, 255, 255, 255, 0, 5, 23,
+        191, 147, 237, 66, 113, 195, 46, 146, 3, 105, 94, 186, 253, 185, 114, 44, 26, 32, 98, 211,
+        151, 110, 149, 73, 89, 226, 18, 13, 26, 104, 105, 131, 54, 174, 58, 193, 191, 116, 119, 236,
+        133, 212, 241, 24, 163, 106, 0, 52, 23, 34, 109, 239, 208, 155, 18, 228, 121, 213, 234, 36,
+        128, 115, 152, 73, 223, 40, 178, 39, 163, 255, 235, 32, 192, 0,
+    ];
+
+    fn model(material_textures: Vec<&str>) -> Model {
+        Model {
+            file_url: String::from("model/m.pbm"),
+            material_base_url: String::from("tex/"),
+            material_textures: material_textures.iter().map(|t| t.to_string()).collect(),
+            model_type: 1,
+        }
+    }
+
+    #[test]
+    fn pbm_chunk_to_mesh() {
+        let mut chunks: Vec<ModelChunk> = Vec::new();
+        decode_chunks(&PBM, &mut chunks).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].name, "chunk-1");
+        assert_eq!(chunks[0].indices, vec![0, 1, 2]);
+
+        let (mesh, materials) = chunks_to_mesh(&chunks, &model(vec!["a.jpg", "b.jpg"]), Path::new("o")).unwrap();
+        assert_eq!(mesh.positions, vec![[1.0, 3.0, -2.0], [4.0, 6.0, -5.0], [7.0, 9.0, -8.0]]);
+        assert_eq!(mesh.uvs, vec![[0.0, 0.75], [1.0, 1.0], [0.0, 0.0]]);
+        assert_eq!(mesh.groups[0].corners, vec![(0, Some(0), None), (1, Some(1), None), (2, Some(2), None)]);
+        let material = &materials[&mesh.groups[0].material];
+        assert_eq!(material.texture.as_deref(), Some(Path::new("o/tex/b.jpg")));
+
+        // without material textures the chunk names its own
+        let (mesh, materials) = chunks_to_mesh(&chunks, &model(vec![]), Path::new("o")).unwrap();
+        let material = &materials[&mesh.groups[0].material];
+        assert_eq!(material.texture.as_deref(), Some(Path::new("o/tex/own.jpg")));
+    }
+
+    #[test]
+    fn at3d_holds_pbm_streams() {
+        let streams = split_at3d(&AT3D).unwrap();
+        assert_eq!(streams.len(), 1);
+        assert_eq!(lzma::decompress(streams[0]).unwrap(), PBM.to_vec());
+        assert!(split_at3d(&AT3D[..50]).is_err());
+    }
+
+    #[test]
+    fn invalid_face_index() {
+        let mut chunks: Vec<ModelChunk> = Vec::new();
+        decode_chunks(&PBM, &mut chunks).unwrap();
+        chunks[0].indices = vec![0, 1, 3];
+        assert!(chunks_to_mesh(&chunks, &model(vec![]), Path::new("o")).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use crate::command::work::PanoramaItem;
+use crate::util::cube;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+pub const PANORAMA_IMAGE_DIR: &str = "images";
+
+// convert every equirectangular image into `<dir>/images/<index>/<face>.jpg`
+pub fn convert_equirect_list(
+    images: &Vec<String>,
+    dir: &str,
+    size: u32,
+) -> Result<Vec<PanoramaItem>, String> {
+    let mut list: Vec<PanoramaItem> = Vec::new();
+    for (index, image) in images.iter().enumerate() {
+        let face_dir = format!("{}/{}", PANORAMA_IMAGE_DIR, index);
+        cube::equirect_to_cube_files(image, Path::new(dir).join(&face_dir).to_str().unwrap(), size)?;
+        list.push(PanoramaItem::from_face_dir(index as i64, &face_dir));
+    }
+    Ok(list)
+}
+
+#[tauri::command]
+pub async fn convert_equirect_to_cube(
+    images: Vec<String>,
+    dir: String,
+    size: u32,
+) -> Result<Vec<PanoramaItem>, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || convert_equirect_list(&images, &dir, size)).await;
+    match result {
+        Ok(Ok(list)) => Ok(list),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
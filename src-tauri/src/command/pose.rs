@@ -0,0 +1,259 @@
+use crate::command::work::{read_local_work, Observer, Quaternion, Work, ORIGIN_DIR};
+use crate::util::cube::face_axes;
+use crate::util::gltf::{GltfCamera, GltfDocument, GltfNode};
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+const DEFAULT_FACE_SIZE: u32 = 1024;
+
+// rotation whose columns are the given axes
+fn from_axes(x: [f64; 3], y: [f64; 3], z: [f64; 3]) -> Quaternion {
+    let trace = x[0] + y[1] + z[2];
+    if trace > 0.0 {
+        let s = (trace + 1.0).sqrt() * 2.0;
+        Quaternion {
+            w: s / 4.0,
+            x: (y[2] - z[1]) / s,
+            y: (z[0] - x[2]) / s,
+            z: (x[1] - y[0]) / s,
+        }
+    } else if x[0] > y[1] && x[0] > z[2] {
+        let s = (1.0 + x[0] - y[1] - z[2]).sqrt() * 2.0;
+        Quaternion {
+            w: (y[2] - z[1]) / s,
+            x: s / 4.0,
+            y: (y[0] + x[1]) / s,
+            z: (z[0] + x[2]) / s,
+        }
+    } else if y[1] > z[2] {
+        let s = (1.0 + y[1] - x[0] - z[2]).sqrt() * 2.0;
+        Quaternion {
+            w: (z[0] - x[2]) / s,
+            x: (y[0] + x[1]) / s,
+            y: s / 4.0,
+            z: (z[1] + y[2]) / s,
+        }
+    } else {
+        let s = (1.0 + z[2] - x[0] - y[1]).sqrt() * 2.0;
+        Quaternion {
+            w: (x[1] - y[0]) / s,
+            x: (z[0] + x[2]) / s,
+            y: (z[1] + y[2]) / s,
+            z: s / 4.0,
+        }
+    }
+}
+
+// orientation of the camera of a cube face in the panorama frame, with the
+// faces laid out as `face_direction` samples them and the three.js camera
+// convention (looking down -Z with +Y up)
+fn face_rotation(face: &str) -> Quaternion {
+    let [right, down, forward] = face_axes(face);
+    from_axes(right, down.map(|v| -v), forward.map(|v| -v))
+}
+
+fn face_names(work: &Work, index: usize) -> Vec<(&'static str, String)> {
+    let item = work
+        .panorama
+        .list
+        .iter()
+        .find(|p| p.index == index as i64)
+        .or(work.panorama.list.get(index));
+    match item {
+        Some(item) => vec![
+            ("right", item.right.clone()),
+            ("left", item.left.clone()),
+            ("front", item.front.clone()),
+            ("back", item.back.clone()),
+            ("up", item.up.clone()),
+            ("down", item.down.clone()),
+        ],
+        None => Vec::new(),
+    }
+}
+
+fn guess_face_size(work: &Work, dir: &str) -> u32 {
+    if let Some(item) = work.panorama.list.first() {
+        let path = Path::new(dir).join(ORIGIN_DIR).join(&item.front);
+        if let Ok((width, _)) = image::image_dimensions(path) {
+            return width;
+        }
+    }
+    DEFAULT_FACE_SIZE
+}
+
+// camera-to-world rotation of a cube face camera, looking down -Z with +Y up as
+// in three.js and glTF
+fn camera_rotation(observer: &Observer, face: &str) -> Quaternion {
+    observer.quaternion.multiply(&face_rotation(face))
+}
+
+// COLMAP stores world-to-camera poses with the camera looking down +Z and +Y down
+fn colmap_pose(observer: &Observer, face: &str) -> (Quaternion, Vec<f64>) {
+    let flip = Quaternion::from_axis_angle([1.0, 0.0, 0.0], PI);
+    let camera_to_world = camera_rotation(observer, face).multiply(&flip);
+    let world_to_camera = camera_to_world.conjugate();
+    let t = world_to_camera.rotate(&observer.position);
+    (world_to_camera, vec![-t[0], -t[1], -t[2]])
+}
+
+pub fn write_colmap(work: &Work, dest_dir: &str, face_size: u32) -> Result<(), String> {
+    if let Err(err) = fs::create_dir_all(dest_dir) {
+        return Err(err.to_string());
+    }
+    let half = face_size as f64 / 2.0;
+    let cameras = format!(
+        "# Camera list with one line of data per camera:\n#   CAMERA_ID, MODEL, WIDTH, HEIGHT, PARAMS[]\n# Number of cameras: 1\n1 SIMPLE_PINHOLE {} {} {} {} {}\n",
+        face_size, face_size, half, half, half
+    );
+    let mut images = String::from(
+        "# Image list with two lines of data per image:\n#   IMAGE_ID, QW, QX, QY, QZ, TX, TY, TZ, CAMERA_ID, NAME\n#   POINTS2D[] as (X, Y, POINT3D_ID)\n",
+    );
+    let mut image_id = 0;
+    for (index, observer) in work.observers.iter().enumerate() {
+        for (face, name) in face_names(work, index) {
+            image_id = image_id + 1;
+            let (q, t) = colmap_pose(observer, face);
+            images.push_str(&format!(
+                "{} {} {} {} {} {} {} {} 1 {}\n\n",
+                image_id, q.w, q.x, q.y, q.z, t[0], t[1], t[2], name
+            ));
+        }
+    }
+    let files = [
+        ("cameras.txt", cameras),
+        ("images.txt", images),
+        ("points3D.txt", String::from("# 3D point list is empty, poses only\n")),
+    ];
+    for (file_name, content) in files.iter() {
+        if let Err(err) = fs::write(Path::new(dest_dir).join(file_name), content.as_bytes()) {
+            return Err(format!("write {} error {}", file_name, err));
+        }
+    }
+    Ok(())
+}
+
+pub fn write_csv(work: &Work, dest: &str) -> Result<(), String> {
+    let mut content = String::from("index,floor_index,x,y,z,standing_x,standing_y,standing_z,qw,qx,qy,qz\n");
+    for observer in work.observers.iter() {
+        let p = &observer.position;
+        let s = &observer.standing_position;
+        let q = &observer.quaternion;
+        content.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            observer.index,
+            observer.floor_index,
+            p.get(0).unwrap_or(&0.0),
+            p.get(1).unwrap_or(&0.0),
+            p.get(2).unwrap_or(&0.0),
+            s.get(0).unwrap_or(&0.0),
+            s.get(1).unwrap_or(&0.0),
+            s.get(2).unwrap_or(&0.0),
+            q.w,
+            q.x,
+            q.y,
+            q.z
+        ));
+    }
+    if let Err(err) = fs::write(dest, content.as_bytes()) {
+        return Err(format!("write `{}` error {}", dest, err));
+    }
+    Ok(())
+}
+
+pub fn write_gltf(work: &Work, dest: &str) -> Result<(), String> {
+    let mut document = GltfDocument::new();
+    document.cameras.push(GltfCamera {
+        name: String::from("panorama"),
+        yfov: (work.initial.fov as f64).to_radians(),
+        aspect_ratio: None,
+    });
+    for observer in work.observers.iter() {
+        let p = &observer.position;
+        // the camera of the front face, the view the viewer opens on
+        let q = camera_rotation(observer, "front");
+        document.nodes.push(GltfNode {
+            name: format!("pano_{}_floor_{}", observer.index, observer.floor_index),
+            mesh: None,
+            camera: Some(0),
+            translation: Some([
+                *p.get(0).unwrap_or(&0.0),
+                *p.get(1).unwrap_or(&0.0),
+                *p.get(2).unwrap_or(&0.0),
+            ]),
+            rotation: Some([q.x, q.y, q.z, q.w]),
+        });
+    }
+    if let Err(err) = fs::write(dest, document.to_glb()) {
+        return Err(format!("write `{}` error {}", dest, err));
+    }
+    Ok(())
+}
+
+pub fn export_poses(dir: &str, dest: &str, format: &str, face_size: u32) -> Result<(), String> {
+    let work = read_local_work(dir)?;
+    match format {
+        "colmap" => {
+            let face_size = if face_size == 0 {
+                guess_face_size(&work, dir)
+            } else {
+                face_size
+            };
+            write_colmap(&work, dest, face_size)
+        }
+        "csv" => write_csv(&work, dest),
+        "gltf" | "glb" => write_gltf(&work, dest),
+        _ => Err(format!("unknown pose format `{}`", format)),
+    }
+}
+
+#[tauri::command]
+pub async fn export_observer_poses(
+    dir: String,
+    dest: String,
+    format: String,
+    face_size: u32,
+) -> Result<String, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || export_poses(&dir, &dest, &format, face_size)).await;
+    match result {
+        Ok(Ok(_)) => Ok(String::from("ok")),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{camera_rotation, colmap_pose};
+    use crate::command::work::{Observer, Quaternion};
+
+    #[test]
+    fn gltf_and_colmap_agree_on_forward() {
+        let observer = Observer {
+            accessible_nodes: vec![],
+            floor_index: 0,
+            index: 0,
+            offset_point_count: 0,
+            position: vec![1.0, 2.0, 3.0],
+            quaternion: Quaternion::from_axis_angle([0.0, 1.0, 0.0], 0.7)
+                .multiply(&Quaternion::from_axis_angle([1.0, 0.0, 0.0], 0.3)),
+            standing_position: vec![1.0, 0.5, 3.0],
+            visible_nodes: vec![],
+        };
+        // glTF cameras look down -Z, COLMAP ones down +Z of the camera frame
+        let gltf = camera_rotation(&observer, "front").rotate(&[0.0, 0.0, -1.0]);
+        let (world_to_camera, t) = colmap_pose(&observer, "front");
+        let colmap = world_to_camera.conjugate().rotate(&[0.0, 0.0, 1.0]);
+        for i in 0..3 {
+            assert!((gltf[i] - colmap[i]).abs() < 1e-9, "{:?} != {:?}", gltf, colmap);
+        }
+        // and the COLMAP center is the observer position
+        let center = world_to_camera.conjugate().rotate(&t);
+        for i in 0..3 {
+            assert!((center[i] + observer.position[i]).abs() < 1e-9);
+        }
+    }
+}
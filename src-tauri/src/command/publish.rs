@@ -0,0 +1,184 @@
+use crate::command::redact::has_unredacted_src_pano;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, InvokeError};
+
+const PROFILE_FILE: &str = "publish_profiles.json";
+pub const KIND_SFTP: &str = "sftp";
+
+// a saved upload target, `kind` tells which publisher uses it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PublishProfile {
+    pub name: String,
+    pub kind: String,
+    #[serde(default)]
+    pub host: String,
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub username: String,
+    pub password: Option<String>,
+    pub private_key_path: Option<String>,
+    // directory on sftp, key prefix on s3
+    #[serde(default)]
+    pub remote_dir: String,
+    pub endpoint: Option<String>,
+    pub bucket: Option<String>,
+    pub region: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    // set in `list_publish_profiles`, the secrets themselves never go back to the webview
+    #[serde(default, skip_deserializing)]
+    pub has_password: bool,
+    #[serde(default, skip_deserializing)]
+    pub has_secret_access_key: bool,
+}
+
+impl PublishProfile {
+    fn redacted(mut self) -> PublishProfile {
+        self.has_password = self.password.is_some();
+        self.has_secret_access_key = self.secret_access_key.is_some();
+        self.password = None;
+        self.secret_access_key = None;
+        self
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct PublishProgress {
+    pub dir: String,
+    pub file: String,
+    pub done_files: usize,
+    pub total_files: usize,
+    pub skipped_files: usize,
+    pub done_bytes: u64,
+    pub total_bytes: u64,
+}
+
+fn profile_path(app: &AppHandle) -> Result<PathBuf, String> {
+    match app.path_resolver().app_data_dir() {
+        Some(dir) => Ok(dir.join(PROFILE_FILE)),
+        None => Err(String::from("app data dir not found")),
+    }
+}
+
+pub fn read_profiles(app: &AppHandle) -> Result<Vec<PublishProfile>, String> {
+    let path = profile_path(app)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path);
+    if let Err(err) = content {
+        return Err(format!("read {} error: {}", PROFILE_FILE, err));
+    }
+    match serde_json::from_str(&content.unwrap()) {
+        Ok(list) => Ok(list),
+        Err(err) => Err(format!("json_decode {} error: {}", PROFILE_FILE, err)),
+    }
+}
+
+fn write_profiles(app: &AppHandle, list: &Vec<PublishProfile>) -> Result<(), String> {
+    let path = profile_path(app)?;
+    if let Err(err) = fs::create_dir_all(path.parent().unwrap()) {
+        return Err(err.to_string());
+    }
+    if let Err(err) = fs::write(&path, serde_json::to_string_pretty(list).unwrap().as_bytes()) {
+        return Err(format!("write {} error: {}", PROFILE_FILE, err));
+    }
+    // passwords and keys are in there, readable by the user only
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        _ = fs::set_permissions(&path, fs::Permissions::from_mode(0o600));
+    }
+    Ok(())
+}
+
+// an sftp upload without a remote directory would write to the server's root
+pub fn check_profile(profile: &PublishProfile) -> Result<(), String> {
+    if profile.name.trim().is_empty() {
+        return Err(String::from("publish profile has no name"));
+    }
+    if profile.kind == KIND_SFTP && profile.remote_dir.trim().trim_end_matches('/').is_empty() {
+        return Err(format!("sftp profile `{}` needs a remote directory", profile.name));
+    }
+    Ok(())
+}
+
+// a profile edited from the list comes back without its secrets, the saved
+// ones are kept, an empty string removes a secret
+fn keep_secrets(profile: &mut PublishProfile, saved: Option<&PublishProfile>) {
+    if profile.password.is_none() {
+        profile.password = saved.and_then(|p| p.password.clone());
+    }
+    if profile.secret_access_key.is_none() {
+        profile.secret_access_key = saved.and_then(|p| p.secret_access_key.clone());
+    }
+    profile.password = profile.password.take().filter(|p| !p.is_empty());
+    profile.secret_access_key = profile.secret_access_key.take().filter(|k| !k.is_empty());
+}
+
+pub fn find_profile(app: &AppHandle, name: &str) -> Result<PublishProfile, String> {
+    match read_profiles(app)?.into_iter().find(|p| p.name == name) {
+        Some(profile) => Ok(profile),
+        None => Err(format!("publish profile `{}` not found", name)),
+    }
+}
+
+// the `preview` or `origin` directory of a work
+pub fn publish_source_dir(dir: &str, source: &str) -> Result<String, String> {
+    if source != "preview" && source != "origin" {
+        return Err(format!("unknown publish source `{}`", source));
+    }
+    if source == "origin" && has_unredacted_src_pano(dir) {
+        return Err(String::from(
+            "`origin` holds the unredacted src_pano of a redacted work, publish `preview` or download it again without src_pano",
+        ));
+    }
+    let path = Path::new(dir).join(source);
+    if !path.is_dir() {
+        return Err(format!("`{}` does not exist", path.display()));
+    }
+    Ok(path.to_str().unwrap().to_string())
+}
+
+#[tauri::command]
+pub fn list_publish_profiles(app: AppHandle) -> Result<Vec<PublishProfile>, InvokeError> {
+    match read_profiles(&app) {
+        Ok(list) => Ok(list.into_iter().map(|p| p.redacted()).collect()),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[tauri::command]
+pub fn save_publish_profile(app: AppHandle, mut profile: PublishProfile) -> Result<String, InvokeError> {
+    if let Err(err) = check_profile(&profile) {
+        return Err(InvokeError::from(err));
+    }
+    let list = read_profiles(&app);
+    if let Err(err) = list {
+        return Err(InvokeError::from(err));
+    }
+    let list = list.unwrap();
+    let saved = list.iter().find(|p| p.name == profile.name).cloned();
+    keep_secrets(&mut profile, saved.as_ref());
+    let mut list: Vec<PublishProfile> = list.into_iter().filter(|p| p.name != profile.name).collect();
+    list.push(profile);
+    match write_profiles(&app, &list) {
+        Ok(_) => Ok(String::from("ok")),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[tauri::command]
+pub fn delete_publish_profile(app: AppHandle, name: String) -> Result<String, InvokeError> {
+    let list = read_profiles(&app);
+    if let Err(err) = list {
+        return Err(InvokeError::from(err));
+    }
+    let list: Vec<PublishProfile> = list.unwrap().into_iter().filter(|p| p.name != name).collect();
+    match write_profiles(&app, &list) {
+        Ok(_) => Ok(String::from("ok")),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
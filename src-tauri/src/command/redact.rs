@@ -0,0 +1,390 @@
+use crate::command::branding::write_branded_faces;
+use crate::command::thumbnail::refresh_thumbnails;
+use crate::command::transcode::encode;
+use crate::command::work::{
+    detect_content_type, read_local_work, read_selection, with_jsonp_suffix, write_jsonp_file,
+    write_raw_files, with_work_locked, Work, ORIGIN_DIR, PREVIEW_DIR,
+};
+use crate::util::cube::{face_direction, CUBE_FACES};
+use crate::util::transcode::{format_from_name, FORMAT_JPEG};
+use image::{imageops, DynamicImage, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+const REDACTION_FILE: &str = "redactions.json";
+// faces of `origin/` as they were before the first redaction, every redaction
+// starts from these so applying the list again does not blur twice
+pub const ORIGINALS_DIR: &str = "redact_originals";
+const REDACTED_QUALITY: u8 = 92;
+// default blur sigma relative to the face width
+const BLUR_RATIO: f32 = 0.02;
+
+pub const SPACE_FACE: &str = "face";
+pub const SPACE_EQUIRECT: &str = "equirect";
+pub const MODE_BLUR: &str = "blur";
+pub const MODE_FILL: &str = "fill";
+
+// a rectangle to hide on one panorama, `x`, `y`, `width` and `height` are in
+// [0, 1] of the face image, or of the equirectangular panorama where `x`
+// may wrap around past 1
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Redaction {
+    pub panorama: i64,
+    #[serde(default = "default_space")]
+    pub space: String,
+    // one of `CUBE_FACES` when `space` is `face`
+    pub face: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    #[serde(default = "default_mode")]
+    pub mode: String,
+    pub color: Option<[u8; 3]>,
+    pub sigma: Option<f32>,
+}
+
+fn default_space() -> String {
+    String::from(SPACE_EQUIRECT)
+}
+
+fn default_mode() -> String {
+    String::from(MODE_BLUR)
+}
+
+pub fn read_redactions(dir: &str) -> Vec<Redaction> {
+    match fs::read_to_string(Path::new(dir).join(REDACTION_FILE)) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// src_pano is unpacked into `origin/` as the host packed it, no redaction
+// reaches it
+pub fn has_unredacted_src_pano(dir: &str) -> bool {
+    read_selection(dir.to_string()).src_pano && !read_redactions(dir).is_empty()
+}
+
+fn write_redactions(dir: &str, list: &Vec<Redaction>) -> Result<(), String> {
+    if let Err(err) = fs::write(
+        Path::new(dir).join(REDACTION_FILE),
+        serde_json::to_string_pretty(list).unwrap().as_bytes(),
+    ) {
+        return Err(format!("write {} error: {}", REDACTION_FILE, err));
+    }
+    Ok(())
+}
+
+fn validate(redaction: &Redaction) -> Result<(), String> {
+    if redaction.space != SPACE_FACE && redaction.space != SPACE_EQUIRECT {
+        return Err(format!("unknown redaction space `{}`", redaction.space));
+    }
+    if redaction.mode != MODE_BLUR && redaction.mode != MODE_FILL {
+        return Err(format!("unknown redaction mode `{}`", redaction.mode));
+    }
+    if redaction.space == SPACE_FACE {
+        match &redaction.face {
+            Some(face) if CUBE_FACES.contains(&face.as_str()) => {}
+            _ => return Err(String::from("face redaction needs one of the cube faces")),
+        }
+    }
+    if redaction.width <= 0.0 || redaction.height <= 0.0 {
+        return Err(String::from("redaction rectangle is empty"));
+    }
+    Ok(())
+}
+
+fn in_range(value: f64, start: f64, length: f64, wrap: bool) -> bool {
+    if wrap {
+        (value - start).rem_euclid(1.0) <= length
+    } else {
+        value >= start && value <= start + length
+    }
+}
+
+// whether pixel (i, j) of a `size` face falls into the rectangle
+fn covers(redaction: &Redaction, face: &str, i: u32, j: u32, width: u32, height: u32) -> bool {
+    let u = (i as f64 + 0.5) / width as f64;
+    let v = (j as f64 + 0.5) / height as f64;
+    if redaction.space == SPACE_FACE {
+        return redaction.face.as_deref() == Some(face)
+            && in_range(u, redaction.x, redaction.width, false)
+            && in_range(v, redaction.y, redaction.height, false);
+    }
+    // same projection as `render_cube_face`
+    let (x, y, z) = face_direction(face, 2.0 * u - 1.0, 2.0 * v - 1.0);
+    let longitude = x.atan2(z);
+    let latitude = y.atan2((x * x + z * z).sqrt());
+    let eu = longitude / (2.0 * PI) + 0.5;
+    let ev = 0.5 - latitude / PI;
+    in_range(eu, redaction.x, redaction.width, true) && in_range(ev, redaction.y, redaction.height, false)
+}
+
+fn redact_face(img: &mut RgbImage, face: &str, redactions: &Vec<&Redaction>) -> bool {
+    let (width, height) = img.dimensions();
+    let mut changed = false;
+    for redaction in redactions.iter() {
+        let mask: Vec<(u32, u32)> = (0..height)
+            .flat_map(|j| (0..width).map(move |i| (i, j)))
+            .filter(|(i, j)| covers(redaction, face, *i, *j, width, height))
+            .collect();
+        if mask.is_empty() {
+            continue;
+        }
+        if redaction.mode == MODE_FILL {
+            let color = Rgb(redaction.color.unwrap_or([0, 0, 0]));
+            for (i, j) in mask {
+                img.put_pixel(i, j, color);
+            }
+        } else {
+            let sigma = redaction.sigma.unwrap_or(width as f32 * BLUR_RATIO).max(1.0);
+            let blurred = imageops::blur(img, sigma);
+            for (i, j) in mask {
+                img.put_pixel(i, j, *blurred.get_pixel(i, j));
+            }
+        }
+        changed = true;
+    }
+    changed
+}
+
+// the unredacted face, saved aside the first time it is redacted
+fn read_original(dir: &str, name: &str) -> Result<RgbImage, String> {
+    let original = Path::new(dir).join(ORIGINALS_DIR).join(name);
+    if !original.exists() {
+        if let Err(err) = fs::create_dir_all(original.parent().unwrap()) {
+            return Err(err.to_string());
+        }
+        if let Err(err) = fs::copy(Path::new(dir).join(ORIGIN_DIR).join(name), &original) {
+            return Err(format!("keep original `{}` error: {}", name, err));
+        }
+    }
+    match image::open(&original) {
+        Ok(img) => Ok(img.to_rgb8()),
+        Err(err) => Err(format!("open face `{}` error: {}", name, err)),
+    }
+}
+
+// a transcode renamed or re-encoded a face, its original goes the same way so
+// the next redaction starts from the unredacted face again
+pub fn transcode_original(
+    dir: &str,
+    from_name: &str,
+    to_name: &str,
+    transcode: &dyn Fn(&[u8]) -> Result<Vec<u8>, String>,
+) -> Result<(), String> {
+    let originals_path = Path::new(dir).join(ORIGINALS_DIR);
+    let data = match fs::read(originals_path.join(from_name)) {
+        Ok(data) => data,
+        Err(_) => return Ok(()),
+    };
+    let output = transcode(&data)?;
+    if let Err(err) = fs::write(originals_path.join(to_name), &output) {
+        return Err(format!("write original `{}` error: {}", to_name, err));
+    }
+    if from_name != to_name {
+        _ = fs::remove_file(originals_path.join(from_name));
+    }
+    Ok(())
+}
+
+// a download replaced these faces of `origin/`, their saved originals are stale,
+// None forgets every face
+pub fn forget_originals(dir: &str, names: Option<&HashSet<String>>) -> Result<(), String> {
+    let originals_path = Path::new(dir).join(ORIGINALS_DIR);
+    match names {
+        None => {
+            if originals_path.exists() {
+                if let Err(err) = fs::remove_dir_all(&originals_path) {
+                    return Err(format!("remove `{}` error: {}", originals_path.display(), err));
+                }
+            }
+        }
+        Some(names) => {
+            for name in names.iter() {
+                _ = fs::remove_file(originals_path.join(name));
+            }
+        }
+    }
+    Ok(())
+}
+
+// rewrites the faces in `origin/` from their originals with every redaction of
+// their panorama and returns the names of the changed files
+fn redact_origin(work: &Work, dir: &str, redactions: &Vec<Redaction>) -> Result<HashSet<String>, String> {
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let mut by_panorama: HashMap<i64, Vec<&Redaction>> = HashMap::new();
+    for redaction in redactions.iter() {
+        by_panorama.entry(redaction.panorama).or_default().push(redaction);
+    }
+    let mut changed: HashSet<String> = HashSet::new();
+    for item in work.panorama.list.iter() {
+        let list = match by_panorama.get(&item.index) {
+            Some(list) => list,
+            None => continue,
+        };
+        let faces = [&item.right, &item.left, &item.front, &item.back, &item.up, &item.down];
+        for (face, name) in CUBE_FACES.iter().zip(faces) {
+            let mut img = read_original(dir, name)?;
+            if !redact_face(&mut img, face, list) {
+                continue;
+            }
+            let format = format_from_name(name).unwrap_or(FORMAT_JPEG);
+            let content = encode(&DynamicImage::ImageRgb8(img), format, REDACTED_QUALITY)?;
+            if let Err(err) = fs::write(origin_path.join(name), &content) {
+                return Err(format!("write `{}` error: {}", name, err));
+            }
+            changed.insert(name.clone());
+        }
+    }
+    Ok(changed)
+}
+
+// regenerate the jsonp files of the edited faces, or the branded copies the
+// raw layout loads
+fn refresh_preview(work: &Work, dir: &str, changed: &HashSet<String>) -> Result<(), String> {
+    if read_selection(dir.to_string()).raw {
+        return write_raw_files(work, dir);
+    }
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let preview_path = Path::new(dir).join(PREVIEW_DIR);
+    for (index, (_, name)) in work.get_download_list().iter().enumerate() {
+        if !changed.contains(name) {
+            continue;
+        }
+        let content = fs::read(origin_path.join(name));
+        if let Err(err) = content {
+            return Err(format!("read `{}` error: {}", name, err));
+        }
+        let content = content.unwrap();
+        let jsonp_dest = with_jsonp_suffix(preview_path.join(name).to_str().unwrap(), index);
+        write_jsonp_file(&detect_content_type(&content, "", name), &content, &jsonp_dest, index)?;
+    }
+    write_branded_faces(work, dir)
+}
+
+pub fn apply_redactions(dir: &str, redactions: &Vec<Redaction>) -> Result<usize, String> {
+    for redaction in redactions.iter() {
+        validate(redaction)?;
+    }
+    let work = read_local_work(dir)?;
+    let changed = redact_origin(&work, dir, redactions)?;
+    refresh_preview(&work, dir, &changed)?;
+    Ok(changed.len())
+}
+
+// the sidecar keeps every redaction so a re-download can apply them again, the
+// saved ones are applied with the new ones since faces start from their originals
+pub fn add_redactions(dir: &str, redactions: Vec<Redaction>) -> Result<usize, String> {
+    for redaction in redactions.iter() {
+        validate(redaction)?;
+    }
+    let mut list = read_redactions(dir);
+    list.extend(redactions);
+    let changed = apply_redactions(dir, &list)?;
+    write_redactions(dir, &list)?;
+    if read_selection(dir.to_string()).src_pano {
+        println!("`{}` keeps its src_pano unredacted in origin, `origin` can not be published", dir);
+    }
+    refresh_thumbnails(dir);
+    Ok(changed)
+}
+
+pub fn reapply_redactions(dir: &str) -> Result<usize, String> {
+    let list = read_redactions(dir);
+    if list.is_empty() {
+        return Ok(0);
+    }
+    let changed = apply_redactions(dir, &list)?;
+    refresh_thumbnails(dir);
+    Ok(changed)
+}
+
+#[tauri::command]
+pub async fn add_work_redactions(dir: String, redactions: Vec<Redaction>) -> Result<usize, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || with_work_locked(&dir, || add_redactions(&dir, redactions))).await;
+    match result {
+        Ok(Ok(changed)) => Ok(changed),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[tauri::command]
+pub fn list_work_redactions(dir: String) -> Vec<Redaction> {
+    read_redactions(&dir)
+}
+
+#[tauri::command]
+pub async fn reapply_work_redactions(dir: String) -> Result<usize, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || with_work_locked(&dir, || reapply_redactions(&dir))).await;
+    match result {
+        Ok(Ok(changed)) => Ok(changed),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_redactions, reapply_redactions, redact_face, Redaction, ORIGINALS_DIR, REDACTED_QUALITY};
+    use crate::command::transcode::{encode, transcode_image, transcode_work};
+    use crate::command::work::tests::sample;
+    use crate::command::work::{write_jsonp_from_origin, write_selection, Selection, ORIGIN_DIR};
+    use crate::util::cube::CUBE_FACES;
+    use crate::util::transcode::{TranscodeProfile, FORMAT_WEBP};
+    use image::{DynamicImage, Rgb, RgbImage};
+    use std::fs;
+
+    #[test]
+    fn transcode_between_redactions() {
+        let dir = std::env::temp_dir().join("redact_transcode_between_redactions");
+        _ = fs::remove_dir_all(&dir);
+        let dir_str = dir.to_str().unwrap();
+        let faces = dir.join(ORIGIN_DIR).join("images/0");
+        fs::create_dir_all(&faces).unwrap();
+        let checker = RgbImage::from_fn(16, 16, |i, j| if (i + j) % 2 == 0 { Rgb([255, 255, 255]) } else { Rgb([0, 0, 0]) });
+        let source = encode(&DynamicImage::ImageRgb8(checker), "jpeg", 90).unwrap();
+        for face in CUBE_FACES.iter() {
+            fs::write(faces.join(format!("{}.jpg", face)), &source).unwrap();
+        }
+        fs::write(dir.join("input.json"), serde_json::to_string(&sample()).unwrap()).unwrap();
+        let selection = Selection {
+            panoramas: Some(vec![0]),
+            model: false,
+            pictures: false,
+            ..Selection::default()
+        };
+        write_selection(dir_str, &selection).unwrap();
+        write_jsonp_from_origin(&sample().select(&selection), dir_str).unwrap();
+
+        let redaction: Redaction = serde_json::from_str(
+            "{\"panorama\":0,\"space\":\"face\",\"face\":\"front\",\"x\":0.25,\"y\":0.25,\"width\":0.5,\"height\":0.5}",
+        )
+        .unwrap();
+        assert_eq!(add_redactions(dir_str, vec![redaction.clone()]).unwrap(), 1);
+
+        // the original follows the face to its new name and format
+        let profile: TranscodeProfile = serde_json::from_str("{\"format\":\"webp\"}").unwrap();
+        transcode_work(dir_str, &profile).unwrap();
+        let originals = dir.join(ORIGINALS_DIR).join("images/0");
+        assert!(!originals.join("front.jpg").exists());
+        let original = fs::read(originals.join("front.webp")).unwrap();
+        assert_eq!(original, transcode_image(&source, FORMAT_WEBP, &profile).unwrap());
+
+        // blurred once from that original, however often it is applied
+        let mut expected = image::load_from_memory(&original).unwrap().to_rgb8();
+        redact_face(&mut expected, "front", &vec![&redaction]);
+        let expected = encode(&DynamicImage::ImageRgb8(expected), FORMAT_WEBP, REDACTED_QUALITY).unwrap();
+        for _ in 0..2 {
+            assert_eq!(reapply_redactions(dir_str).unwrap(), 1);
+            assert_eq!(fs::read(faces.join("front.webp")).unwrap(), expected);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use crate::command::viewer::write_viewer_files;
+use crate::command::work::{create_file_directory, read_local_work, Work, ORIGIN_DIR};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tauri::InvokeError;
+use tokio;
+
+fn is_absolute_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://") || url.starts_with("//")
+}
+
+// asset paths must be relative to base_url, which is how they are stored in origin/
+fn to_relative(old_base_url: &str, url: &str) -> String {
+    if !old_base_url.is_empty() && url.starts_with(old_base_url) {
+        return url[old_base_url.len()..].to_string();
+    }
+    if is_absolute_url(url) {
+        // drop the host, keep the path
+        let path = url.splitn(2, "//").nth(1).unwrap_or("");
+        return path.splitn(2, '/').nth(1).unwrap_or("").to_string();
+    }
+    url.trim_start_matches('/').to_string()
+}
+
+// absolute form of `path` with links and `..` resolved, also when the end of it
+// does not exist yet
+fn resolve_path(path: &Path) -> Result<PathBuf, String> {
+    let mut resolved = match std::env::current_dir() {
+        Ok(current) => current,
+        Err(err) => return Err(format!("resolve `{}` error: {}", path.display(), err)),
+    };
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            other => resolved.push(other),
+        }
+        if let Ok(real) = resolved.canonicalize() {
+            resolved = real;
+        }
+    }
+    Ok(resolved)
+}
+
+pub fn get_hosted_work(work: &Work, base_url: &str) -> Work {
+    let mut base_url = base_url.to_string();
+    if !base_url.ends_with('/') {
+        base_url.push('/');
+    }
+    let old_base_url = work.base_url.clone();
+    let relative = |url: &str| to_relative(&old_base_url, url);
+    let mut hosted = work.clone();
+    hosted.base_url = base_url.clone();
+    if !work.picture_url.is_empty() {
+        hosted.picture_url = format!("{}picture.jpg", base_url);
+    }
+    if !work.title_picture_url.is_empty() {
+        hosted.title_picture_url = format!("{}title_picture.jpg", base_url);
+    }
+    for item in hosted.panorama.list.iter_mut() {
+        item.right = relative(&item.right);
+        item.left = relative(&item.left);
+        item.front = relative(&item.front);
+        item.back = relative(&item.back);
+        item.up = relative(&item.up);
+        item.down = relative(&item.down);
+    }
+    hosted.model.file_url = relative(&work.model.file_url);
+    hosted.model.material_base_url = relative(&work.model.material_base_url);
+    hosted
+}
+
+// write the package to upload as is into `dest`: the assets of origin/, work.json
+// for the new base url, work.js and the viewer files for opening index.html over
+// http, origin/ itself stays untouched so the local preview keeps working
+pub fn write_hosted_work(dir: &str, base_url: &str, dest: &str) -> Result<Work, String> {
+    if !is_absolute_url(base_url) && !base_url.starts_with('/') {
+        return Err(format!("base url `{}` must be absolute", base_url));
+    }
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let dest_path = Path::new(dest);
+    if dest.is_empty() {
+        return Err(String::from("export dir is empty"));
+    }
+    let (resolved_dest, resolved_dir) = (resolve_path(dest_path)?, resolve_path(Path::new(dir))?);
+    if resolved_dest.starts_with(&resolved_dir) || resolved_dir.starts_with(&resolved_dest) {
+        return Err(format!("export dir `{}` must be outside the work `{}`", dest, dir));
+    }
+    let work = read_local_work(dir)?;
+    let hosted = get_hosted_work(&work, base_url);
+    for (_, name) in hosted.get_download_list().iter() {
+        if !origin_path.join(name).exists() {
+            return Err(format!("`{}` is missing in origin", name));
+        }
+    }
+    for (_, name) in hosted.get_download_list().iter() {
+        let target = dest_path.join(name);
+        create_file_directory(target.to_str().unwrap())?;
+        if let Err(err) = fs::copy(origin_path.join(name), &target) {
+            return Err(format!("copy `{}` error: {}", name, err));
+        }
+    }
+    let work_json = serde_json::to_string(&hosted).unwrap();
+    if let Err(err) = fs::write(dest_path.join("work.json"), work_json.as_bytes()) {
+        return Err(format!("write work.json error {}", err));
+    }
+    let work_js = format!("var workJSON = {}", work_json);
+    if let Err(err) = fs::write(dest_path.join("work.js"), work_js.as_bytes()) {
+        return Err(format!("write work.js error {}", err));
+    }
+    write_viewer_files(&hosted, dir, dest_path)?;
+    Ok(hosted)
+}
+
+#[tauri::command]
+pub async fn export_work_for_hosting(dir: String, base_url: String, dest: String) -> Result<Work, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || write_hosted_work(&dir, &base_url, &dest)).await;
+    match result {
+        Ok(Ok(work)) => Ok(work),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_path, write_hosted_work};
+    use std::fs;
+
+    #[test]
+    fn export_dir_inside_the_work() {
+        let root = std::env::temp_dir().join("rehost_export_dir_inside_the_work");
+        let work = root.join("work");
+        fs::create_dir_all(&work).unwrap();
+        let work_str = work.to_str().unwrap();
+        let resolved = resolve_path(&work.join("not/yet")).unwrap();
+        assert_eq!(resolved, work.canonicalize().unwrap().join("not/yet"));
+
+        // the same dir written another way, neither exists yet
+        let dest = root.join("other/../work/export");
+        let err = write_hosted_work(work_str, "https://example.com/", dest.to_str().unwrap()).unwrap_err();
+        assert!(err.contains("must be outside"), "{}", err);
+        let err = write_hosted_work(work_str, "https://example.com/", root.to_str().unwrap()).unwrap_err();
+        assert!(err.contains("must be outside"), "{}", err);
+        #[cfg(unix)]
+        {
+            let link = root.join("link");
+            _ = fs::remove_file(&link);
+            std::os::unix::fs::symlink(&work, &link).unwrap();
+            let dest = link.join("export");
+            let err = write_hosted_work(work_str, "https://example.com/", dest.to_str().unwrap()).unwrap_err();
+            assert!(err.contains("must be outside"), "{}", err);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+use crate::command::publish::{find_profile, publish_source_dir, PublishProfile, PublishProgress};
+use crate::util::file;
+use crate::util::mime::mime_type;
+use crate::util::s3::{md5_hex, multipart_etag, S3Client};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, InvokeError, Window};
+use tokio;
+use tokio::sync::Semaphore;
+
+const DEFAULT_THREADS: usize = 4;
+const MAX_THREADS: usize = 16;
+// files from this size on, mostly the tar bundles, go up in parts
+const MULTIPART_THRESHOLD: u64 = 16 * 1024 * 1024;
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+fn required<'a>(value: &'a Option<String>, name: &str) -> Result<&'a str, String> {
+    match value.as_deref() {
+        Some(value) if !value.is_empty() => Ok(value),
+        _ => Err(format!("s3 profile has no `{}`", name)),
+    }
+}
+
+pub fn s3_client(profile: &PublishProfile) -> Result<S3Client, String> {
+    Ok(S3Client::new(
+        required(&profile.endpoint, "endpoint")?,
+        required(&profile.bucket, "bucket")?,
+        profile.region.as_deref().unwrap_or(""),
+        required(&profile.access_key_id, "access_key_id")?,
+        required(&profile.secret_access_key, "secret_access_key")?,
+    ))
+}
+
+// returns false when the object already has the same content,
+// large files are never read into memory as a whole
+async fn upload_object(client: &S3Client, local: &Path, key: &str, size: u64) -> Result<bool, String> {
+    let content_type = mime_type(key);
+    if size >= MULTIPART_THRESHOLD {
+        let path = local.to_path_buf();
+        let etag = tokio::task::spawn_blocking(move || {
+            File::open(&path).and_then(|file| multipart_etag(BufReader::new(file), PART_SIZE))
+        })
+        .await;
+        let etag = match etag {
+            Ok(Ok(etag)) => etag,
+            Ok(Err(err)) => return Err(format!("read `{}` error: {}", local.display(), err)),
+            Err(err) => return Err(err.to_string()),
+        };
+        if client.head_etag(key).await? == Some(etag) {
+            return Ok(false);
+        }
+        client.put_multipart(key, content_type, local, size, PART_SIZE).await?;
+        return Ok(true);
+    }
+    let data = tokio::fs::read(local).await;
+    if let Err(err) = data {
+        return Err(format!("read `{}` error: {}", local.display(), err));
+    }
+    let data = data.unwrap();
+    if client.head_etag(key).await? == Some(md5_hex(&data)) {
+        return Ok(false);
+    }
+    client.put_object(key, content_type, data).await?;
+    Ok(true)
+}
+
+pub async fn upload_dir(
+    profile: &PublishProfile,
+    local_dir: &str,
+    threads: usize,
+    on_progress: Arc<dyn Fn(&PublishProgress) + Send + Sync>,
+) -> Result<PublishProgress, String> {
+    let client = s3_client(profile)?;
+    let files = file::list_files(local_dir)?;
+    let progress = Arc::new(Mutex::new(PublishProgress {
+        dir: local_dir.to_string(),
+        total_files: files.len(),
+        total_bytes: files.iter().map(|f| f.1).sum(),
+        ..PublishProgress::default()
+    }));
+    let semaphore = Arc::new(Semaphore::new(threads.clamp(1, MAX_THREADS)));
+    let prefix = profile.remote_dir.trim_matches('/').to_string();
+
+    let mut handles = Vec::new();
+    for (name, size) in files {
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let client = client.clone();
+        let progress = progress.clone();
+        let on_progress = on_progress.clone();
+        let local = Path::new(local_dir).join(&name);
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+        handles.push(tokio::spawn(async move {
+            let result = upload_object(&client, &local, &key, size).await;
+            drop(permit);
+            let uploaded = result?;
+            let mut state = progress.lock().unwrap();
+            state.file = name;
+            state.done_files = state.done_files + 1;
+            state.done_bytes = state.done_bytes + size;
+            if !uploaded {
+                state.skipped_files = state.skipped_files + 1;
+            }
+            on_progress(&state);
+            Ok::<(), String>(())
+        }));
+    }
+    let mut errors: Vec<String> = Vec::new();
+    for handle in handles {
+        match handle.await {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => errors.push(err),
+            Err(err) => errors.push(err.to_string()),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    let result = progress.lock().unwrap().clone();
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn publish_work_s3(
+    app: AppHandle,
+    window: Window,
+    dir: String,
+    profile: String,
+    source: String,
+    threads: Option<usize>,
+) -> Result<PublishProgress, InvokeError> {
+    let profile = find_profile(&app, &profile);
+    if let Err(err) = profile {
+        return Err(InvokeError::from(err));
+    }
+    let local_dir = publish_source_dir(&dir, &source);
+    if let Err(err) = local_dir {
+        return Err(InvokeError::from(err));
+    }
+    let on_progress = Arc::new(move |progress: &PublishProgress| {
+        _ = window.emit("publish_progress", progress.clone());
+    });
+    match upload_dir(
+        &profile.unwrap(),
+        &local_dir.unwrap(),
+        threads.unwrap_or(DEFAULT_THREADS),
+        on_progress,
+    )
+    .await
+    {
+        Ok(progress) => Ok(progress),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
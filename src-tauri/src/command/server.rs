@@ -0,0 +1,145 @@
+use crate::command::publish::publish_source_dir;
+use crate::command::work::{ORIGIN_DIR, PREVIEW_DIR};
+use crate::util::static_server;
+use reqwest;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tauri::{AppHandle, InvokeError, Manager, WindowBuilder, WindowUrl};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PreviewServer {
+    pub port: u16,
+    pub dir: String,
+    pub root: String,
+    pub url: String,
+}
+
+lazy_static! {
+    static ref SERVERS: Arc<Mutex<HashMap<u16, (PreviewServer, Arc<AtomicBool>)>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+// port 0 lets the system pick a free one, the same root is only served once
+pub fn start_server(dir: &str, source: &str, port: u16) -> Result<PreviewServer, String> {
+    let root = publish_source_dir(dir, source)?;
+    let mut servers = SERVERS.lock().unwrap();
+    if let Some((server, _)) = servers.values().find(|(s, _)| s.root == root) {
+        return Ok(server.clone());
+    }
+    let listener = TcpListener::bind(("127.0.0.1", port));
+    if let Err(err) = listener {
+        return Err(format!("listen on port {} error: {}", port, err));
+    }
+    let listener = listener.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = PreviewServer {
+        port,
+        dir: dir.to_string(),
+        root: root.clone(),
+        url: format!("http://127.0.0.1:{}/", port),
+    };
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = stop.clone();
+    thread::spawn(move || static_server::serve(listener, PathBuf::from(root), flag));
+    servers.insert(port, (server.clone(), stop));
+    Ok(server)
+}
+
+pub fn stop_server(port: u16) -> Result<(), String> {
+    match SERVERS.lock().unwrap().remove(&port) {
+        Some((_, stop)) => {
+            stop.store(true, Ordering::SeqCst);
+            static_server::wake(port);
+            Ok(())
+        }
+        None => Err(format!("no preview server on port {}", port)),
+    }
+}
+
+// `window` opens a tauri window, `browser` the system browser
+fn open_server(app: &AppHandle, server: &PreviewServer, target: &str) -> Result<(), String> {
+    match target {
+        "window" => {
+            let label = format!("preview_{}", server.port);
+            if let Some(window) = app.get_window(&label) {
+                _ = window.set_focus();
+                return Ok(());
+            }
+            let url = reqwest::Url::parse(&server.url).unwrap();
+            if let Err(err) = WindowBuilder::new(app, label, WindowUrl::External(url))
+                .title(server.dir.clone())
+                .inner_size(1280.0, 800.0)
+                .build()
+            {
+                return Err(format!("open preview window error: {}", err));
+            }
+            Ok(())
+        }
+        "browser" => match tauri::api::shell::open(&app.shell_scope(), &server.url, None) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!("open browser error: {}", err)),
+        },
+        _ => Err(format!("unknown open target `{}`", target)),
+    }
+}
+
+#[tauri::command]
+pub fn start_preview_server(
+    app: AppHandle,
+    dir: String,
+    source: Option<String>,
+    port: Option<u16>,
+    open: Option<String>,
+) -> Result<PreviewServer, InvokeError> {
+    // raw layout works have no `preview/`, their viewer sits in `origin/`
+    let source = source.unwrap_or_else(|| {
+        if Path::new(&dir).join(PREVIEW_DIR).exists() {
+            String::from(PREVIEW_DIR)
+        } else {
+            String::from(ORIGIN_DIR)
+        }
+    });
+    let server = start_server(&dir, &source, port.unwrap_or(0));
+    if let Err(err) = server {
+        return Err(InvokeError::from(err));
+    }
+    let server = server.unwrap();
+    if let Some(target) = open {
+        if let Err(err) = open_server(&app, &server, &target) {
+            return Err(InvokeError::from(err));
+        }
+    }
+    Ok(server)
+}
+
+#[tauri::command]
+pub fn open_preview_server(app: AppHandle, port: u16, target: String) -> Result<String, InvokeError> {
+    let server = SERVERS.lock().unwrap().get(&port).map(|(s, _)| s.clone());
+    match server {
+        Some(server) => match open_server(&app, &server, &target) {
+            Ok(_) => Ok(String::from("ok")),
+            Err(err) => Err(InvokeError::from(err)),
+        },
+        None => Err(InvokeError::from(format!("no preview server on port {}", port))),
+    }
+}
+
+#[tauri::command]
+pub fn stop_preview_server(port: u16) -> Result<String, InvokeError> {
+    match stop_server(port) {
+        Ok(_) => Ok(String::from("ok")),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[tauri::command]
+pub fn list_preview_servers() -> Vec<PreviewServer> {
+    let mut list: Vec<PreviewServer> = SERVERS.lock().unwrap().values().map(|(s, _)| s.clone()).collect();
+    list.sort_by_key(|s| s.port);
+    list
+}
@@ -0,0 +1,294 @@
+use crate::command::publish::{check_profile, find_profile, publish_source_dir, PublishProfile, PublishProgress};
+use crate::util::file;
+use ssh2::{FileStat, OpenFlags, OpenType, Session};
+use std::fs::File;
+use std::io::{copy, Seek, SeekFrom};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, InvokeError, Window};
+use tokio;
+
+const DEFAULT_SSH_PORT: u16 = 22;
+const DEFAULT_THREADS: usize = 4;
+const MAX_THREADS: usize = 16;
+
+pub fn connect(profile: &PublishProfile) -> Result<Session, String> {
+    let port = profile.port.unwrap_or(DEFAULT_SSH_PORT);
+    let tcp = TcpStream::connect((profile.host.as_str(), port));
+    if let Err(err) = tcp {
+        return Err(format!("connect {}:{} error: {}", profile.host, port, err));
+    }
+    let session = Session::new();
+    if let Err(err) = session {
+        return Err(err.to_string());
+    }
+    let mut session = session.unwrap();
+    session.set_tcp_stream(tcp.unwrap());
+    if let Err(err) = session.handshake() {
+        return Err(format!("ssh handshake error: {}", err));
+    }
+    // with a key file the password is used as its passphrase
+    let result = match (&profile.private_key_path, &profile.password) {
+        (Some(key), passphrase) if !key.is_empty() => session.userauth_pubkey_file(
+            &profile.username,
+            None,
+            Path::new(key),
+            passphrase.as_deref(),
+        ),
+        (_, Some(password)) => session.userauth_password(&profile.username, password),
+        _ => session.userauth_agent(&profile.username),
+    };
+    if let Err(err) = result {
+        return Err(format!("ssh auth error: {}", err));
+    }
+    if !session.authenticated() {
+        return Err(String::from("ssh auth failed"));
+    }
+    Ok(session)
+}
+
+fn mkdir_all(sftp: &ssh2::Sftp, dir: &Path) {
+    let mut current = std::path::PathBuf::new();
+    for part in dir.components() {
+        current.push(part);
+        if sftp.stat(&current).is_err() {
+            _ = sftp.mkdir(&current, 0o755);
+        }
+    }
+}
+
+fn modified_secs(file: &File) -> Option<u64> {
+    let modified = file.metadata().ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+#[derive(Debug, PartialEq)]
+enum UploadMode {
+    Skip,
+    Resume,
+    Restart,
+}
+
+// `remote` is the size and mtime of the remote file when there is one
+fn upload_mode(size: u64, mtime: Option<u64>, remote: Option<(u64, Option<u64>)>) -> UploadMode {
+    let (remote_size, remote_mtime) = match remote {
+        Some(remote) => remote,
+        None => return UploadMode::Restart,
+    };
+    if remote_size == size && mtime.is_some() && remote_mtime == mtime {
+        return UploadMode::Skip;
+    }
+    // resume a partial upload, it was written after the local file last changed,
+    // restart when the remote file is bigger or older
+    let resume = remote_size > 0
+        && remote_size < size
+        && matches!((remote_mtime, mtime), (Some(remote_mtime), Some(mtime)) if remote_mtime > mtime);
+    if resume {
+        UploadMode::Resume
+    } else {
+        UploadMode::Restart
+    }
+}
+
+// remote paths always use `/`, whatever the local platform
+fn remote_path(remote_dir: &str, name: &str) -> String {
+    format!("{}/{}", remote_dir.trim_end_matches('/'), name.replace('\\', "/"))
+}
+
+// returns false when the remote file is already complete, a complete upload
+// carries the mtime of its local file so a change of the same size is still sent
+fn upload_file(sftp: &ssh2::Sftp, local: &Path, remote: &Path, size: u64) -> Result<bool, String> {
+    let local_file = File::open(local);
+    if let Err(err) = local_file {
+        return Err(format!("open `{}` error: {}", local.display(), err));
+    }
+    let mut local_file = local_file.unwrap();
+    let mtime = modified_secs(&local_file);
+    let stat = sftp.stat(remote).ok();
+    let remote_size = stat.as_ref().and_then(|s| s.size).unwrap_or(0);
+    let resume = match upload_mode(size, mtime, stat.as_ref().map(|s| (remote_size, s.mtime))) {
+        UploadMode::Skip => return Ok(false),
+        UploadMode::Resume => true,
+        UploadMode::Restart => false,
+    };
+    if let Some(parent) = remote.parent() {
+        mkdir_all(sftp, parent);
+    }
+    let flags = if resume {
+        OpenFlags::WRITE
+    } else {
+        OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE
+    };
+    let remote_file = sftp.open_mode(remote, flags, 0o644, OpenType::File);
+    if let Err(err) = remote_file {
+        return Err(format!("open remote `{}` error: {}", remote.display(), err));
+    }
+    let mut remote_file = remote_file.unwrap();
+    if resume {
+        if let Err(err) = remote_file.seek(SeekFrom::Start(remote_size)) {
+            return Err(err.to_string());
+        }
+        if let Err(err) = local_file.seek(SeekFrom::Start(remote_size)) {
+            return Err(err.to_string());
+        }
+    }
+    if let Err(err) = copy(&mut local_file, &mut remote_file) {
+        return Err(format!("upload `{}` error: {}", local.display(), err));
+    }
+    drop(remote_file);
+    if let Some(mtime) = mtime {
+        let times = FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: None,
+            atime: Some(mtime),
+            mtime: Some(mtime),
+        };
+        if let Err(err) = sftp.setstat(remote, times) {
+            return Err(format!("set mtime of remote `{}` error: {}", remote.display(), err));
+        }
+    }
+    Ok(true)
+}
+
+pub fn upload_dir(
+    profile: &PublishProfile,
+    local_dir: &str,
+    threads: usize,
+    on_progress: Arc<dyn Fn(&PublishProgress) + Send + Sync>,
+) -> Result<PublishProgress, String> {
+    // profiles saved before the check could still point at `/`
+    check_profile(profile)?;
+    let files = file::list_files(local_dir)?;
+    let progress = Arc::new(Mutex::new(PublishProgress {
+        dir: local_dir.to_string(),
+        total_files: files.len(),
+        total_bytes: files.iter().map(|f| f.1).sum(),
+        ..PublishProgress::default()
+    }));
+    let queue = Arc::new(Mutex::new(files));
+    let errors: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+    let threads = threads.clamp(1, MAX_THREADS);
+
+    let mut handles = Vec::new();
+    for _ in 0..threads {
+        let profile = profile.clone();
+        let local_dir = local_dir.to_string();
+        let queue = queue.clone();
+        let progress = progress.clone();
+        let errors = errors.clone();
+        let on_progress = on_progress.clone();
+        // one ssh session per worker, libssh2 serializes calls on a session
+        handles.push(thread::spawn(move || {
+            let session = match connect(&profile) {
+                Ok(session) => session,
+                Err(err) => return errors.lock().unwrap().push(err),
+            };
+            let sftp = match session.sftp() {
+                Ok(sftp) => sftp,
+                Err(err) => return errors.lock().unwrap().push(err.to_string()),
+            };
+            loop {
+                let job = queue.lock().unwrap().pop();
+                let (name, size) = match job {
+                    Some(job) => job,
+                    None => break,
+                };
+                let local = Path::new(&local_dir).join(&name);
+                let remote = remote_path(&profile.remote_dir, &name);
+                match upload_file(&sftp, &local, Path::new(&remote), size) {
+                    Ok(uploaded) => {
+                        let mut state = progress.lock().unwrap();
+                        state.file = name.clone();
+                        state.done_files = state.done_files + 1;
+                        state.done_bytes = state.done_bytes + size;
+                        if !uploaded {
+                            state.skipped_files = state.skipped_files + 1;
+                        }
+                        on_progress(&state);
+                    }
+                    Err(err) => errors.lock().unwrap().push(err),
+                }
+            }
+        }));
+    }
+    for handle in handles {
+        _ = handle.join();
+    }
+    let errors = errors.lock().unwrap();
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    let result = progress.lock().unwrap().clone();
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn publish_work_sftp(
+    app: AppHandle,
+    window: Window,
+    dir: String,
+    profile: String,
+    source: String,
+    threads: Option<usize>,
+) -> Result<PublishProgress, InvokeError> {
+    let profile = find_profile(&app, &profile);
+    if let Err(err) = profile {
+        return Err(InvokeError::from(err));
+    }
+    let local_dir = publish_source_dir(&dir, &source);
+    if let Err(err) = local_dir {
+        return Err(InvokeError::from(err));
+    }
+    let on_progress = Arc::new(move |progress: &PublishProgress| {
+        _ = window.emit("publish_progress", progress.clone());
+    });
+    let result = tokio::task::spawn_blocking(move || {
+        upload_dir(
+            &profile.unwrap(),
+            &local_dir.unwrap(),
+            threads.unwrap_or(DEFAULT_THREADS),
+            on_progress,
+        )
+    })
+    .await;
+    match result {
+        Ok(Ok(progress)) => Ok(progress),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{remote_path, upload_mode, UploadMode};
+
+    #[test]
+    fn skip_resume_or_restart() {
+        // nothing uploaded yet
+        assert_eq!(upload_mode(100, Some(10), None), UploadMode::Restart);
+        // complete, with the mtime of the local file
+        assert_eq!(upload_mode(100, Some(10), Some((100, Some(10)))), UploadMode::Skip);
+        // same size but changed since
+        assert_eq!(upload_mode(100, Some(20), Some((100, Some(10)))), UploadMode::Restart);
+        // no local mtime, never trust the remote file
+        assert_eq!(upload_mode(100, None, Some((100, None))), UploadMode::Restart);
+        // partial upload newer than the local file
+        assert_eq!(upload_mode(100, Some(10), Some((40, Some(12)))), UploadMode::Resume);
+        // partial upload of an older version
+        assert_eq!(upload_mode(100, Some(10), Some((40, Some(8)))), UploadMode::Restart);
+        // bigger or empty remote file
+        assert_eq!(upload_mode(100, Some(10), Some((140, Some(12)))), UploadMode::Restart);
+        assert_eq!(upload_mode(100, Some(10), Some((0, Some(12)))), UploadMode::Restart);
+    }
+
+    #[test]
+    fn remote_paths_use_slashes() {
+        assert_eq!(remote_path("/var/www/tour/", "images/0/front.jpg"), "/var/www/tour/images/0/front.jpg");
+        assert_eq!(remote_path("/var/www/tour", "images\\0\\front.jpg"), "/var/www/tour/images/0/front.jpg");
+    }
+}
@@ -0,0 +1,266 @@
+use crate::command::branding::read_watermark;
+use crate::command::viewer::viewer_files;
+use crate::command::work::{detect_content_type, read_local_work, Work, ORIGIN_DIR};
+use crate::util::cube::encode_jpeg;
+use base64::{engine::general_purpose, Engine as _};
+use image::imageops::{self, FilterType};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+const WORK_SCRIPT: &str = "<script src=\"./work.js\"></script>";
+const FACE_QUALITY: u8 = 80;
+const MIN_FACE_SCALE: f64 = 0.1;
+const FACE_SCALE_STEP: f64 = 0.85;
+
+// five.js loads `<name>.<n>.jsonp` through a script tag after registering
+// `window.jsonp_<n>`, answer those from the inlined data uris instead
+const JSONP_SHIM: &str = r#"(function () {
+    var src = Object.getOwnPropertyDescriptor(HTMLScriptElement.prototype, 'src');
+    Object.defineProperty(HTMLScriptElement.prototype, 'src', {
+        get: function () { return src.get.call(this); },
+        set: function (url) {
+            var match = String(url).match(/\.([0-9]+)\.jsonp([\?\#].*)?$/i);
+            if (match && workAssets[match[1]] !== undefined) {
+                var callback = 'jsonp_' + match[1];
+                setTimeout(function () { window[callback] && window[callback](workAssets[match[1]]); }, 0);
+                return;
+            }
+            src.set.call(this, url);
+        }
+    });
+})();"#;
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SingleHtmlEstimate {
+    pub viewer_bytes: u64,
+    pub face_bytes: u64,
+    pub other_bytes: u64,
+    pub total_bytes: u64,
+    // 1.0 when the faces are embedded as downloaded
+    pub face_scale: f64,
+}
+
+#[derive(Clone)]
+struct Embedded {
+    content_type: String,
+    data: Vec<u8>,
+    face: bool,
+}
+
+fn base64_len(len: usize) -> u64 {
+    ((len as u64) + 2) / 3 * 4
+}
+
+fn data_uri(asset: &Embedded) -> String {
+    format!(
+        "data:{};base64,{}",
+        asset.content_type,
+        general_purpose::STANDARD.encode(&asset.data)
+    )
+}
+
+// the html parser ends a script at any `</script`, whatever its case, and `<\/`
+// reads the same in js strings and regexes
+fn escape_script(code: &str) -> String {
+    code.replace("</", "<\\/")
+}
+
+fn read_assets(work: &Work, dir: &str) -> Result<Vec<Embedded>, String> {
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let mut faces: HashSet<&str> = HashSet::new();
+    for item in work.panorama.list.iter() {
+        for face in [&item.right, &item.left, &item.front, &item.back, &item.up, &item.down] {
+            faces.insert(face.as_str());
+        }
+    }
+    let watermark = read_watermark(work, dir)?;
+    let mut assets: Vec<Embedded> = Vec::new();
+    for (_, name) in work.get_download_list().iter() {
+        // faces as the viewer shows them, with the watermark of the work
+        if let Some(watermark) = watermark.as_ref().filter(|w| w.applies(name)) {
+            let data = watermark.brand(&origin_path, name)?;
+            assets.push(Embedded {
+                content_type: detect_content_type(&data, "", name),
+                data,
+                face: true,
+            });
+            continue;
+        }
+        let data = fs::read(origin_path.join(name));
+        if let Err(err) = data {
+            return Err(format!("read `{}` error: {}", name, err));
+        }
+        let data = data.unwrap();
+        assets.push(Embedded {
+            content_type: detect_content_type(&data, "", name),
+            data,
+            face: faces.contains(name.as_str()),
+        });
+    }
+    Ok(assets)
+}
+
+fn viewer_bytes(viewer: &HashMap<String, Vec<u8>>) -> u64 {
+    viewer.values().map(|data| data.len() as u64).sum()
+}
+
+fn estimate(assets: &Vec<Embedded>, viewer: &HashMap<String, Vec<u8>>, face_scale: f64) -> SingleHtmlEstimate {
+    let mut result = SingleHtmlEstimate {
+        viewer_bytes: viewer_bytes(viewer),
+        face_scale,
+        ..SingleHtmlEstimate::default()
+    };
+    for asset in assets.iter() {
+        // `"data:<type>;base64,<data>",`
+        let size = base64_len(asset.data.len()) + asset.content_type.len() as u64 + 16;
+        if asset.face {
+            result.face_bytes = result.face_bytes + size;
+        } else {
+            result.other_bytes = result.other_bytes + size;
+        }
+    }
+    result.total_bytes = result.viewer_bytes + result.face_bytes + result.other_bytes;
+    result
+}
+
+fn downscale_faces(assets: &Vec<Embedded>, scale: f64) -> Result<Vec<Embedded>, String> {
+    let mut result = assets.clone();
+    for asset in result.iter_mut().filter(|a| a.face) {
+        let img = image::load_from_memory(&asset.data);
+        if let Err(err) = img {
+            return Err(format!("decode face error: {}", err));
+        }
+        let img = img.unwrap().to_rgb8();
+        let width = ((img.width() as f64 * scale) as u32).max(1);
+        let height = ((img.height() as f64 * scale) as u32).max(1);
+        let resized = imageops::resize(&img, width, height, FilterType::Triangle);
+        asset.data = encode_jpeg(&resized, FACE_QUALITY)?;
+        asset.content_type = String::from("image/jpeg");
+    }
+    Ok(result)
+}
+
+// shrink the faces until the file fits, the first guess assumes bytes follow pixels
+fn fit_assets(
+    assets: &Vec<Embedded>,
+    viewer: &HashMap<String, Vec<u8>>,
+    max_bytes: u64,
+) -> Result<(Vec<Embedded>, f64), String> {
+    let current = estimate(assets, viewer, 1.0);
+    if current.total_bytes <= max_bytes {
+        return Ok((assets.clone(), 1.0));
+    }
+    let fixed = current.viewer_bytes + current.other_bytes;
+    if current.face_bytes == 0 || fixed >= max_bytes {
+        return Err(format!(
+            "viewer and model alone take {} bytes, more than {}",
+            fixed, max_bytes
+        ));
+    }
+    let mut scale = ((max_bytes - fixed) as f64 / current.face_bytes as f64).sqrt().min(1.0);
+    while scale >= MIN_FACE_SCALE {
+        let resized = downscale_faces(assets, scale)?;
+        if estimate(&resized, viewer, scale).total_bytes <= max_bytes {
+            return Ok((resized, scale));
+        }
+        scale = scale * FACE_SCALE_STEP;
+    }
+    Err(format!("faces can not be shrunk below {} bytes", max_bytes))
+}
+
+// the viewer files of the work by name, templated like the ones written to disk
+fn read_viewer(work: &Work, dir: &str) -> Result<HashMap<String, Vec<u8>>, String> {
+    Ok(viewer_files(work, dir)?.into_iter().collect())
+}
+
+// every local script index.html loads is inlined, whatever the template
+fn build_html(work: &Work, viewer: &HashMap<String, Vec<u8>>, assets: &Vec<Embedded>) -> Result<String, String> {
+    let index = match viewer.get("index.html") {
+        Some(index) => index,
+        None => return Err(String::from("viewer has no index.html")),
+    };
+    let mut html = String::from_utf8_lossy(index).to_string();
+    for (name, code) in viewer.iter().filter(|(name, _)| name.ends_with(".js")) {
+        let tag = format!("<script src=\"./{}\"></script>", name);
+        if !html.contains(&tag) {
+            continue;
+        }
+        let inline = format!("<script>{}</script>", escape_script(&String::from_utf8_lossy(code)));
+        html = html.replacen(&tag, &inline, 1);
+    }
+    if !html.contains(WORK_SCRIPT) {
+        return Err(String::from("index.html does not load `work.js`"));
+    }
+    let data: Vec<String> = assets.iter().map(data_uri).collect();
+    let work_script = format!(
+        "<script>var workJSON = {};\nvar workAssets = {};\n{}</script>",
+        escape_script(&work.get_jsonp_work()),
+        serde_json::to_string(&data).unwrap(),
+        JSONP_SHIM
+    );
+    Ok(html.replacen(WORK_SCRIPT, &work_script, 1))
+}
+
+pub fn estimate_html(dir: &str) -> Result<SingleHtmlEstimate, String> {
+    let work = read_local_work(dir)?;
+    let viewer = read_viewer(&work, dir)?;
+    Ok(estimate(&read_assets(&work, dir)?, &viewer, 1.0))
+}
+
+pub fn write_single_html(dir: &str, dest: &str, max_bytes: Option<u64>) -> Result<SingleHtmlEstimate, String> {
+    let work = read_local_work(dir)?;
+    let assets = read_assets(&work, dir)?;
+    let viewer = read_viewer(&work, dir)?;
+    let (assets, scale) = match max_bytes {
+        Some(max_bytes) => fit_assets(&assets, &viewer, max_bytes)?,
+        None => (assets, 1.0),
+    };
+    let html = build_html(&work, &viewer, &assets)?;
+    if let Err(err) = fs::write(dest, html.as_bytes()) {
+        return Err(format!("write `{}` error: {}", dest, err));
+    }
+    let mut result = estimate(&assets, &viewer, scale);
+    result.total_bytes = html.len() as u64;
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn estimate_single_html(dir: String) -> Result<SingleHtmlEstimate, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || estimate_html(&dir)).await;
+    match result {
+        Ok(Ok(estimate)) => Ok(estimate),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn export_single_html(
+    dir: String,
+    dest: String,
+    max_bytes: Option<u64>,
+) -> Result<SingleHtmlEstimate, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || write_single_html(&dir, &dest, max_bytes)).await;
+    match result {
+        Ok(Ok(estimate)) => Ok(estimate),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_script;
+
+    #[test]
+    fn escape_every_closing_tag() {
+        assert_eq!(
+            escape_script("var a = '</SCRIPT>', b = \"</Script >\", c = /<\\/p>/"),
+            "var a = '<\\/SCRIPT>', b = \"<\\/Script >\", c = /<\\/p>/"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use crate::command::work::{
+    create_file_directory, read_local_work, write_jsonp_from_origin, write_preview_files, Work,
+    ORIGIN_DIR,
+};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+// copy the origin files a work refers to into another work directory
+pub fn copy_origin_files(work: &Work, src_dir: &str, dest_dir: &str) -> Result<(), String> {
+    let src_path = Path::new(src_dir).join(ORIGIN_DIR);
+    let dest_path = Path::new(dest_dir).join(ORIGIN_DIR);
+    for (_, name) in work.get_download_list().iter() {
+        let dest = dest_path.join(name);
+        create_file_directory(dest.to_str().unwrap())?;
+        if let Err(err) = fs::copy(src_path.join(name), &dest) {
+            return Err(format!("copy `{}` error: {}", name, err));
+        }
+    }
+    Ok(())
+}
+
+// write a complete work directory: input.json, origin files and preview
+pub fn write_work_package(work: &Work, src_dir: &str, dest_dir: &str) -> Result<(), String> {
+    if let Err(err) = fs::create_dir_all(dest_dir) {
+        return Err(err.to_string());
+    }
+    if let Err(err) = fs::write(
+        Path::new(dest_dir).join("input.json"),
+        serde_json::to_string(work).unwrap().as_bytes(),
+    ) {
+        return Err(format!("write work input.json error:{}", err));
+    }
+    copy_origin_files(work, src_dir, dest_dir)?;
+    write_jsonp_from_origin(work, dest_dir)?;
+    write_preview_files(work, dest_dir)
+}
+
+pub fn split_by_floor(dir: &str, dest_dir: &str) -> Result<Vec<String>, String> {
+    let work = read_local_work(dir)?;
+    let floors: BTreeSet<i64> = work.observers.iter().map(|o| o.floor_index).collect();
+    let mut list: Vec<String> = Vec::new();
+    for floor_index in floors.iter() {
+        let keep: Vec<i64> = work
+            .observers
+            .iter()
+            .filter(|o| o.floor_index == *floor_index)
+            .map(|o| o.index)
+            .collect();
+        let floor_work = work.subset(&keep);
+        let floor_dir = Path::new(dest_dir).join(format!("floor_{}", floor_index));
+        let floor_dir = floor_dir.to_str().unwrap().to_string();
+        write_work_package(&floor_work, dir, &floor_dir)?;
+        list.push(floor_dir);
+    }
+    Ok(list)
+}
+
+#[tauri::command]
+pub async fn split_work_by_floor(dir: String, dest_dir: String) -> Result<Vec<String>, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || split_by_floor(&dir, &dest_dir)).await;
+    match result {
+        Ok(Ok(list)) => Ok(list),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
@@ -0,0 +1,435 @@
+use crate::command::layout::rebuild_preview;
+use crate::command::library::{finish_download, read_source, WorkSource};
+use crate::command::redact::{apply_redactions, forget_originals, read_redactions, Redaction};
+use crate::command::thumbnail::{generate_thumbnails, DEFAULT_THUMBNAIL_SIZE, MODE_FRONT};
+use crate::command::transcode::transcode_files;
+use crate::command::work::{
+    download_file, lock_work, read_local_work, read_selection, read_work, unlock_work, write_selection,
+    PanoramaItem, Selection, Work, ORIGIN_DIR,
+};
+use crate::util::file;
+use crate::util::url::strip_query;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use tauri::{AppHandle, InvokeError};
+use tokio;
+
+const SNAPSHOT_DIR: &str = "snapshots";
+
+// what changed upstream, panoramas by index and files by their name in `origin/`
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct WorkChanges {
+    pub added_panoramas: Vec<i64>,
+    pub removed_panoramas: Vec<i64>,
+    pub changed_panoramas: Vec<i64>,
+    pub model_changed: bool,
+    pub added_textures: Vec<String>,
+    pub removed_textures: Vec<String>,
+    pub pictures_changed: bool,
+    // new or changed files, downloaded by a sync
+    pub files: Vec<String>,
+    // files the new work no longer has
+    pub removed_files: Vec<String>,
+    // directory of the previous version when a snapshot was kept
+    pub snapshot: Option<String>,
+}
+
+impl WorkChanges {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.removed_files.is_empty()
+    }
+}
+
+// (url, name as downloaded, name in `origin/`), names differ when faces were transcoded
+fn local_files(work: &Work, selection: &Selection) -> Vec<(String, String, String)> {
+    let local = match &selection.transcode {
+        Some(profile) => work.transcoded(profile),
+        None => work.clone(),
+    };
+    work.get_download_list()
+        .into_iter()
+        .zip(local.get_download_list())
+        .map(|((url, name), (_, local_name))| (url, name, local_name))
+        .collect()
+}
+
+fn face_list(work: &Work) -> HashMap<i64, Vec<String>> {
+    work.panorama
+        .list
+        .iter()
+        .map(|item| {
+            let faces = [&item.right, &item.left, &item.front, &item.back, &item.up, &item.down];
+            (item.index, faces.iter().map(|f| f.to_string()).collect())
+        })
+        .collect()
+}
+
+fn sorted<T: Ord>(mut list: Vec<T>) -> Vec<T> {
+    list.sort();
+    list
+}
+
+// `old` and `new` are selected the same way, files missing in `origin/` count as changed
+pub fn diff_changes(dir: &str, old: &Work, new: &Work, selection: &Selection) -> WorkChanges {
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let old_files: HashMap<String, String> = old
+        .get_download_list()
+        .into_iter()
+        .map(|(url, name)| (name, strip_query(&url).to_string()))
+        .collect();
+    let new_files = local_files(new, selection);
+    let mut changes = WorkChanges::default();
+    for (url, name, local_name) in new_files.iter() {
+        let same = old_files.get(name).map_or(false, |old_url| old_url == strip_query(url));
+        if !same || !origin_path.join(local_name).exists() {
+            changes.files.push(name.clone());
+        }
+    }
+    let new_local: HashSet<&String> = new_files.iter().map(|f| &f.2).collect();
+    changes.removed_files = local_files(old, selection)
+        .into_iter()
+        .map(|f| f.2)
+        .filter(|name| !new_local.contains(name))
+        .collect();
+
+    let changed: HashSet<&String> = changes.files.iter().collect();
+    let old_faces = face_list(old);
+    let new_faces = face_list(new);
+    for (index, faces) in new_faces.iter() {
+        match old_faces.get(index) {
+            None => changes.added_panoramas.push(*index),
+            Some(old) if old != faces || faces.iter().any(|f| changed.contains(f)) => {
+                changes.changed_panoramas.push(*index)
+            }
+            _ => {}
+        }
+    }
+    changes.removed_panoramas = old_faces.keys().filter(|i| !new_faces.contains_key(i)).cloned().collect();
+    changes.added_panoramas = sorted(changes.added_panoramas);
+    changes.changed_panoramas = sorted(changes.changed_panoramas);
+    changes.removed_panoramas = sorted(changes.removed_panoramas);
+
+    changes.model_changed =
+        old.model.file_url != new.model.file_url || changed.contains(&new.model.file_url);
+    let old_textures: HashSet<&String> = old.model.material_textures.iter().collect();
+    let new_textures: HashSet<&String> = new.model.material_textures.iter().collect();
+    changes.added_textures = sorted(new_textures.difference(&old_textures).map(|t| t.to_string()).collect());
+    changes.removed_textures = sorted(old_textures.difference(&new_textures).map(|t| t.to_string()).collect());
+    changes.pictures_changed = ["picture.jpg", "title_picture.jpg"]
+        .iter()
+        .any(|name| changed.contains(&name.to_string()));
+    changes
+}
+
+fn parse_work(work_json: &str) -> Result<Work, String> {
+    match serde_json::from_str(work_json) {
+        Ok(work) => Ok(work),
+        Err(err) => Err(format!("json_decode_work error:{}", err)),
+    }
+}
+
+fn same_panorama(a: &PanoramaItem, b: &PanoramaItem) -> bool {
+    match (a.derived_id, b.derived_id) {
+        (Some(a), Some(b)) => a == b,
+        _ => strip_query(&a.front) == strip_query(&b.front),
+    }
+}
+
+// `selection.panoramas` holds indexes of the stored work, upstream may renumber
+// its panoramas so the selected ones are found again by derived_id or faces
+fn follow_selection(old: &Work, new: &Work, selection: &Selection) -> Selection {
+    let list = match &selection.panoramas {
+        Some(list) => list,
+        None => return selection.clone(),
+    };
+    let panoramas: Vec<i64> = list
+        .iter()
+        .filter_map(|index| old.panorama.list.iter().find(|p| p.index == *index))
+        .filter_map(|item| new.panorama.list.iter().find(|p| same_panorama(item, p)))
+        .map(|p| p.index)
+        .collect();
+    Selection {
+        panoramas: Some(panoramas),
+        ..selection.clone()
+    }
+}
+
+// the stored and the upstream work with the same panoramas selected, and the
+// selection of the upstream one
+fn selected_works(dir: &str, work_json: &str) -> Result<(Work, Work, Selection), String> {
+    let selection = read_selection(dir.to_string());
+    let old = read_work(dir.to_string())?;
+    let new = parse_work(work_json)?;
+    let new_selection = follow_selection(&old, &new, &selection);
+    Ok((old.select(&selection), new.select(&new_selection), new_selection))
+}
+
+pub fn check_updates(dir: &str, work_json: &str) -> Result<WorkChanges, String> {
+    let (old, new, selection) = selected_works(dir, work_json)?;
+    Ok(diff_changes(dir, &old, &new, &selection))
+}
+
+// input.json, selection.json and `origin/` of the current version, enough to rebuild it
+fn write_snapshot(dir: &str) -> Result<String, String> {
+    let path = Path::new(dir);
+    let name = chrono::Local::now().format("%Y-%m-%d-%H-%M-%S").to_string();
+    let snapshot_path = path.join(SNAPSHOT_DIR).join(&name);
+    let origin_path = path.join(ORIGIN_DIR);
+    let mut files: Vec<String> = vec![String::from("input.json"), String::from("selection.json")];
+    for (name, _) in file::list_files(origin_path.to_str().unwrap())? {
+        files.push(format!("{}/{}", ORIGIN_DIR, name));
+    }
+    for name in files.iter() {
+        if !path.join(name).exists() {
+            continue;
+        }
+        let dest = snapshot_path.join(name);
+        if let Err(err) = fs::create_dir_all(dest.parent().unwrap()) {
+            return Err(err.to_string());
+        }
+        if let Err(err) = fs::copy(path.join(name), &dest) {
+            return Err(format!("snapshot `{}` error: {}", name, err));
+        }
+    }
+    Ok(snapshot_path.to_str().unwrap().to_string())
+}
+
+// everything derived from `origin/` once the new files are in place
+fn finish_sync(dir: &str, new: &Work, changes: &WorkChanges) -> Result<(), String> {
+    let selection = read_selection(dir.to_string());
+    if let Some(profile) = &selection.transcode {
+        let only: HashSet<String> = changes.files.iter().cloned().collect();
+        transcode_files(dir, new, profile, Some(&only))?;
+    }
+    rebuild_preview(dir)?;
+    // faces kept from the previous version are redacted already
+    let touched: HashSet<i64> = changes
+        .added_panoramas
+        .iter()
+        .chain(changes.changed_panoramas.iter())
+        .cloned()
+        .collect();
+    let redactions: Vec<Redaction> = read_redactions(dir)
+        .into_iter()
+        .filter(|r| touched.contains(&r.panorama))
+        .collect();
+    if !redactions.is_empty() {
+        // the fetched faces replace the originals kept by earlier redactions
+        let mut fetched: HashSet<String> = HashSet::new();
+        for item in read_local_work(dir)?.panorama.list.iter().filter(|p| touched.contains(&p.index)) {
+            for face in [&item.right, &item.left, &item.front, &item.back, &item.up, &item.down] {
+                fetched.insert(face.clone());
+            }
+        }
+        forget_originals(dir, Some(&fetched))?;
+        apply_redactions(dir, &redactions)?;
+    }
+    if let Err(err) = generate_thumbnails(dir, MODE_FRONT, DEFAULT_THUMBNAIL_SIZE) {
+        println!("generate_thumbnails error {}", err);
+    }
+    Ok(())
+}
+
+// download only what changed upstream, the stored input.json is replaced by `work_json`,
+// never while a download of the same work is queued or running
+pub async fn sync(app: &AppHandle, dir: &str, work_json: &str, snapshot: bool) -> Result<WorkChanges, String> {
+    lock_work(dir)?;
+    let result = sync_files(app, dir, work_json, snapshot).await;
+    unlock_work(dir);
+    result
+}
+
+async fn sync_files(app: &AppHandle, dir: &str, work_json: &str, snapshot: bool) -> Result<WorkChanges, String> {
+    let (old, new, selection) = selected_works(dir, work_json)?;
+    let mut changes = diff_changes(dir, &old, &new, &selection);
+    // redactions apply to whole panoramas, fetch every face of a redacted one
+    // so none of them gets redacted twice
+    let redacted: HashSet<i64> = read_redactions(dir).iter().map(|r| r.panorama).collect();
+    for item in new.panorama.list.iter() {
+        if !redacted.contains(&item.index) || !changes.changed_panoramas.contains(&item.index) {
+            continue;
+        }
+        for face in [&item.right, &item.left, &item.front, &item.back, &item.up, &item.down] {
+            if !changes.files.contains(face) {
+                changes.files.push(face.clone());
+            }
+        }
+    }
+    if snapshot {
+        let task_dir = dir.to_string();
+        changes.snapshot = match tokio::task::spawn_blocking(move || write_snapshot(&task_dir)).await {
+            Ok(Ok(path)) => Some(path),
+            Ok(Err(err)) => return Err(err),
+            Err(err) => return Err(err.to_string()),
+        };
+    }
+
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let wanted: HashSet<&String> = changes.files.iter().collect();
+    for (index, (url, name)) in new.get_download_list().iter().enumerate() {
+        if wanted.contains(name) {
+            download_file(url.clone(), origin_path.join(name).to_str().unwrap(), None, index).await?;
+        }
+    }
+    for name in changes.removed_files.iter() {
+        let path = origin_path.join(name);
+        _ = fs::remove_file(&path);
+        // only succeeds once the folder of a removed panorama is empty
+        _ = fs::remove_dir(path.parent().unwrap());
+    }
+    // fresh signatures for later downloads even when nothing changed
+    if let Err(err) = fs::write(Path::new(dir).join("input.json"), work_json.as_bytes()) {
+        return Err(format!("write work input.json error:{}", err));
+    }
+    write_selection(dir, &selection)?;
+    if changes.is_empty() {
+        return Ok(changes);
+    }
+
+    let task_dir = dir.to_string();
+    let task_changes = changes.clone();
+    match tokio::task::spawn_blocking(move || finish_sync(&task_dir, &new, &task_changes)).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(err)) => return Err(err),
+        Err(err) => return Err(err.to_string()),
+    }
+    let (task_app, task_dir) = (app.clone(), dir.to_string());
+    match tokio::task::spawn_blocking(move || finish_download(&task_app, &task_dir, true)).await {
+        Ok(Err(err)) => println!("finish_download error {}", err),
+        Err(err) => println!("finish_download error {}", err),
+        Ok(Ok(_)) => {}
+    }
+    Ok(changes)
+}
+
+#[tauri::command]
+pub fn get_work_source(dir: String) -> WorkSource {
+    read_source(&dir)
+}
+
+#[tauri::command]
+pub fn check_work_updates(dir: String, work_json: String) -> Result<WorkChanges, InvokeError> {
+    match check_updates(&dir, &work_json) {
+        Ok(changes) => Ok(changes),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[tauri::command]
+pub async fn sync_work(
+    app: AppHandle,
+    dir: String,
+    work_json: String,
+    snapshot: Option<bool>,
+) -> Result<WorkChanges, InvokeError> {
+    match sync(&app, &dir, &work_json, snapshot.unwrap_or(false)).await {
+        Ok(changes) => Ok(changes),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_changes, follow_selection};
+    use crate::command::work::tests::sample;
+    use crate::command::work::{Selection, Work, ORIGIN_DIR};
+    use std::fs;
+
+    // every file of `work` in `origin/` of a fresh dir
+    fn work_dir(name: &str, work: &Work) -> String {
+        let dir = std::env::temp_dir().join(name);
+        _ = fs::remove_dir_all(&dir);
+        for (_, name) in work.get_download_list() {
+            let path = dir.join(ORIGIN_DIR).join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"x").unwrap();
+        }
+        dir.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn follow_renumbered_panoramas() {
+        let mut old = sample();
+        for item in old.panorama.list.iter_mut() {
+            item.derived_id = Some(100 + item.index);
+        }
+        // upstream dropped the first panorama, renumbered and reordered the rest
+        // and moved their faces
+        let mut new = old.clone();
+        new.panorama.list.remove(0);
+        new.panorama.list.reverse();
+        for (index, item) in new.panorama.list.iter_mut().enumerate() {
+            item.index = index as i64;
+            item.front = format!("v2/{}", item.front);
+        }
+        let selection = Selection {
+            panoramas: Some(vec![0, 1, 3]),
+            ..Selection::default()
+        };
+        // 101 and 103 are now 2 and 0, 100 is gone
+        assert_eq!(follow_selection(&old, &new, &selection).panoramas, Some(vec![2, 0]));
+
+        // without derived_id the front face tells, whatever its query
+        let old = sample();
+        let mut new = sample();
+        new.panorama.list.swap(0, 1);
+        for (index, item) in new.panorama.list.iter_mut().enumerate() {
+            item.index = index as i64;
+            item.front = format!("{}?sign=abc", item.front);
+        }
+        assert_eq!(follow_selection(&old, &new, &selection).panoramas, Some(vec![1, 0, 3]));
+        assert_eq!(follow_selection(&old, &new, &Selection::default()).panoramas, None);
+    }
+
+    #[test]
+    fn resigned_urls_are_unchanged() {
+        let selection = Selection {
+            panoramas: Some(vec![0]),
+            model: false,
+            ..Selection::default()
+        };
+        let old = sample().select(&selection);
+        let dir = work_dir("sync_resigned_urls_are_unchanged", &old);
+        let mut new = old.clone();
+        new.picture_url = format!("{}?Expires=2&Signature=b", old.picture_url);
+        new.title_picture_url = format!("{}?Expires=2&Signature=c", old.title_picture_url);
+        let changes = diff_changes(&dir, &old, &new, &selection);
+        assert!(changes.is_empty(), "{:?}", changes);
+        assert!(!changes.pictures_changed);
+
+        // a file gone from `origin/` is fetched again
+        fs::remove_file(std::path::Path::new(&dir).join(ORIGIN_DIR).join("images/0/up.jpg")).unwrap();
+        let changes = diff_changes(&dir, &old, &new, &selection);
+        assert_eq!(changes.files, vec!["images/0/up.jpg"]);
+        assert_eq!(changes.changed_panoramas, vec![0]);
+
+        // a new picture is
+        new.picture_url = String::from("https://example.com/p2.jpg");
+        let changes = diff_changes(&dir, &old, &new, &selection);
+        assert!(changes.files.contains(&String::from("picture.jpg")));
+        assert!(changes.pictures_changed);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn removed_files() {
+        let selection = Selection::default();
+        let old = sample();
+        let dir = work_dir("sync_removed_files", &old);
+        let mut new = sample();
+        new.panorama.list.pop();
+        new.model.material_textures.remove(0);
+        let changes = diff_changes(&dir, &old, &new, &selection);
+        assert!(changes.files.is_empty());
+        assert_eq!(changes.removed_panoramas, vec![3]);
+        assert_eq!(changes.removed_textures, vec!["t0.jpg"]);
+        let mut removed = changes.removed_files.clone();
+        removed.sort();
+        assert_eq!(removed.len(), 7);
+        assert_eq!(removed[0], "images/3/back.jpg");
+        assert_eq!(removed[6], "model/t0.jpg");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use crate::command::work::{read_local_work, PanoramaItem, Work, ORIGIN_DIR};
+use crate::util::cube::{render_equirect, write_jpeg};
+use crate::util::font::{draw_text, text_width, GLYPH_HEIGHT};
+use image::imageops::{self, FilterType};
+use image::{ImageBuffer, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+pub const THUMBNAIL_DIR: &str = "thumbnails";
+pub const CONTACT_SHEET: &str = "contact_sheet.jpg";
+const MANIFEST: &str = "thumbnails.json";
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+const THUMBNAIL_QUALITY: u8 = 85;
+const LABEL_SCALE: u32 = 2;
+const SHEET_GAP: u32 = 8;
+
+pub const MODE_FRONT: &str = "front";
+pub const MODE_EQUIRECT: &str = "equirect";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Thumbnail {
+    pub index: i64,
+    pub floor_index: i64,
+    // relative to the work directory
+    pub file: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ThumbnailManifest {
+    pub mode: String,
+    pub size: u32,
+    pub contact_sheet: String,
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+fn read_face(dir: &str, name: &str) -> Result<RgbImage, String> {
+    match image::open(Path::new(dir).join(ORIGIN_DIR).join(name)) {
+        Ok(img) => Ok(img.to_rgb8()),
+        Err(err) => Err(format!("open face `{}` error: {}", name, err)),
+    }
+}
+
+// `front` is a square of the front face, `equirect` the whole panorama at 2:1
+fn render_thumbnail(dir: &str, item: &PanoramaItem, mode: &str, size: u32) -> Result<RgbImage, String> {
+    if mode == MODE_EQUIRECT {
+        let mut faces: Vec<RgbImage> = Vec::new();
+        for name in [&item.right, &item.left, &item.front, &item.back, &item.up, &item.down] {
+            faces.push(read_face(dir, name)?);
+        }
+        return Ok(render_equirect(&faces, size * 2));
+    }
+    let front = read_face(dir, &item.front)?;
+    Ok(imageops::resize(&front, size, size, FilterType::Triangle))
+}
+
+fn floor_of(work: &Work, index: i64) -> i64 {
+    work.observers
+        .iter()
+        .find(|o| o.index == index)
+        .map(|o| o.floor_index)
+        .unwrap_or(0)
+}
+
+// thumbnails in a grid, each labelled `#<index> F<floor>` underneath
+fn render_contact_sheet(thumbnails: &Vec<(Thumbnail, RgbImage)>) -> RgbImage {
+    let count = thumbnails.len().max(1) as u32;
+    let columns = (count as f64).sqrt().ceil() as u32;
+    let rows = (count + columns - 1) / columns;
+    let cell_width = thumbnails.iter().map(|t| t.1.width()).max().unwrap_or(1);
+    let label_height = GLYPH_HEIGHT * LABEL_SCALE + SHEET_GAP;
+    let cell_height = thumbnails.iter().map(|t| t.1.height()).max().unwrap_or(1) + label_height;
+    let mut sheet: RgbImage = ImageBuffer::from_pixel(
+        columns * (cell_width + SHEET_GAP) + SHEET_GAP,
+        rows * (cell_height + SHEET_GAP) + SHEET_GAP,
+        Rgb([32, 32, 32]),
+    );
+    for (n, (thumbnail, img)) in thumbnails.iter().enumerate() {
+        let x = SHEET_GAP + (n as u32 % columns) * (cell_width + SHEET_GAP);
+        let y = SHEET_GAP + (n as u32 / columns) * (cell_height + SHEET_GAP);
+        imageops::replace(&mut sheet, img, x as i64, y as i64);
+        let label = format!("#{} F{}", thumbnail.index, thumbnail.floor_index);
+        let label_x = x + cell_width.saturating_sub(text_width(&label, LABEL_SCALE)) / 2;
+        draw_text(
+            &mut sheet,
+            label_x,
+            y + img.height() + SHEET_GAP / 2,
+            &label,
+            LABEL_SCALE,
+            Rgb([255, 255, 255]),
+        );
+    }
+    sheet
+}
+
+pub fn generate_thumbnails(dir: &str, mode: &str, size: u32) -> Result<ThumbnailManifest, String> {
+    if mode != MODE_FRONT && mode != MODE_EQUIRECT {
+        return Err(format!("unknown thumbnail mode `{}`", mode));
+    }
+    let work = read_local_work(dir)?;
+    let thumbnail_path = Path::new(dir).join(THUMBNAIL_DIR);
+    let mut thumbnails: Vec<(Thumbnail, RgbImage)> = Vec::new();
+    for item in work.panorama.list.iter() {
+        let img = render_thumbnail(dir, item, mode, size)?;
+        let file = format!("{}/{}.jpg", THUMBNAIL_DIR, item.index);
+        write_jpeg(
+            &img,
+            thumbnail_path.join(format!("{}.jpg", item.index)).to_str().unwrap(),
+            THUMBNAIL_QUALITY,
+        )?;
+        let thumbnail = Thumbnail {
+            index: item.index,
+            floor_index: floor_of(&work, item.index),
+            file,
+        };
+        thumbnails.push((thumbnail, img));
+    }
+    thumbnails.sort_by_key(|t| (t.0.floor_index, t.0.index));
+    let sheet = render_contact_sheet(&thumbnails);
+    write_jpeg(
+        &sheet,
+        thumbnail_path.join(CONTACT_SHEET).to_str().unwrap(),
+        THUMBNAIL_QUALITY,
+    )?;
+    let manifest = ThumbnailManifest {
+        mode: mode.to_string(),
+        size,
+        contact_sheet: format!("{}/{}", THUMBNAIL_DIR, CONTACT_SHEET),
+        thumbnails: thumbnails.into_iter().map(|t| t.0).collect(),
+    };
+    if let Err(err) = fs::write(
+        thumbnail_path.join(MANIFEST),
+        serde_json::to_string(&manifest).unwrap().as_bytes(),
+    ) {
+        return Err(format!("write {} error: {}", MANIFEST, err));
+    }
+    Ok(manifest)
+}
+
+// regenerate the thumbnails after the faces changed, with the mode and size
+// they were made with, never fails the caller
+pub fn refresh_thumbnails(dir: &str) {
+    let manifest_path = Path::new(dir).join(THUMBNAIL_DIR).join(MANIFEST);
+    let manifest: Option<ThumbnailManifest> = fs::read_to_string(manifest_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+    let (mode, size) = match &manifest {
+        Some(manifest) => (manifest.mode.as_str(), manifest.size),
+        None => (MODE_FRONT, DEFAULT_THUMBNAIL_SIZE),
+    };
+    if let Err(err) = generate_thumbnails(dir, mode, size) {
+        println!("generate_thumbnails error {}", err);
+    }
+}
+
+#[tauri::command]
+pub async fn generate_work_thumbnails(
+    dir: String,
+    mode: Option<String>,
+    size: Option<u32>,
+) -> Result<ThumbnailManifest, InvokeError> {
+    let mode = mode.unwrap_or(String::from(MODE_FRONT));
+    let size = size.unwrap_or(DEFAULT_THUMBNAIL_SIZE);
+    let result = tokio::task::spawn_blocking(move || generate_thumbnails(&dir, &mode, size)).await;
+    match result {
+        Ok(Ok(manifest)) => Ok(manifest),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
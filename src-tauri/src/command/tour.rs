@@ -0,0 +1,180 @@
+use crate::command::graph::{initial_pano, NavGraph};
+use crate::command::viewer::viewer_files;
+use crate::command::work::{read_local_work, read_selection, Work, ORIGIN_DIR, PREVIEW_DIR};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::InvokeError;
+
+const DEFAULT_INTERVAL: u64 = 6000;
+// written next to the viewer index.html
+pub const TOUR_FILES: [&str; 2] = ["tour.js", "tour.html"];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Walkthrough {
+    path: Vec<i64>,
+    distance: f64,
+    unreachable: Vec<i64>,
+    interval: u64,
+    #[serde(rename = "loop")]
+    loop_tour: bool,
+}
+
+// played by tour.html, `window.five` is set up by the bundled index.html
+const TOUR_PLAYER: &str = r#";(function () {
+    var tour = tourJSON
+    var step = 0
+    var play = function () {
+        if (tour.path.length < 2) return
+        step = step + 1
+        if (step >= tour.path.length) {
+            if (!tour.loop) return
+            step = 0
+        }
+        window.five.moveToPano(tour.path[step])
+        window.five.once('panoArrived', function () {
+            setTimeout(play, tour.interval)
+        })
+    }
+    window.five.once('panoArrived', function () {
+        if (window.five.panoIndex !== tour.path[0]) {
+            step = -1
+        }
+        setTimeout(play, tour.interval)
+    })
+})();
+"#;
+
+pub fn read_walkthrough(dir: &str) -> Option<Walkthrough> {
+    let content = fs::read_to_string(Path::new(dir).join("tour.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+// the viewer sits in `origin/` for the raw layout and in `preview/` otherwise
+fn viewer_path(dir: &str) -> PathBuf {
+    if read_selection(dir.to_string()).raw {
+        return Path::new(dir).join(ORIGIN_DIR);
+    }
+    Path::new(dir).join(PREVIEW_DIR)
+}
+
+fn tour_html(index_html: &[u8]) -> String {
+    String::from_utf8_lossy(index_html).replace(
+        "</body>",
+        "<script src=\"./tour.js\"></script>\n</body>",
+    )
+}
+
+pub fn write_tour_files(work: &Work, dir: &str, walkthrough: &Walkthrough) -> Result<(), String> {
+    let path = Path::new(dir);
+    let viewer_path = viewer_path(dir);
+    let tour_json = serde_json::to_string(walkthrough).unwrap();
+    if let Err(err) = fs::write(path.join("tour.json"), tour_json.as_bytes()) {
+        return Err(format!("write tour.json error {}", err));
+    }
+    let script = format!("var tourJSON = {}\n{}", tour_json, TOUR_PLAYER);
+    if let Err(err) = fs::write(viewer_path.join("tour.js"), script.as_bytes()) {
+        return Err(format!("write tour.js error {}", err));
+    }
+    let index_html = viewer_files(work, dir)?.into_iter().find(|f| f.0 == "index.html");
+    let index_html = match index_html {
+        Some((_, data)) => data,
+        None => return Err(String::from("viewer has no index.html")),
+    };
+    if let Err(err) = fs::write(viewer_path.join("tour.html"), tour_html(&index_html).as_bytes()) {
+        return Err(format!("write tour.html error {}", err));
+    }
+    Ok(())
+}
+
+// without `to` the walkthrough visits every reachable panorama
+#[tauri::command]
+pub fn generate_walkthrough(
+    dir: String,
+    from: Option<i64>,
+    to: Option<i64>,
+    interval: Option<u64>,
+    loop_tour: Option<bool>,
+) -> Result<Walkthrough, InvokeError> {
+    let work = read_local_work(&dir);
+    if let Err(err) = work {
+        return Err(InvokeError::from(err));
+    }
+    let work = work.unwrap();
+    let graph = NavGraph::from_work(&work);
+    let from = from.unwrap_or(initial_pano(&work));
+    if !graph.floors.contains_key(&from) {
+        return Err(InvokeError::from(format!("panorama {} not found", from)));
+    }
+    let (path, distance, unreachable) = match to {
+        Some(to) => match graph.shortest_path(from, to) {
+            Some((path, distance)) => (path, distance, Vec::new()),
+            None => {
+                return Err(InvokeError::from(format!(
+                    "panorama {} can not be reached from {}",
+                    to, from
+                )))
+            }
+        },
+        None => graph.tour(from),
+    };
+    let walkthrough = Walkthrough {
+        path: path,
+        distance: distance,
+        unreachable: unreachable,
+        interval: interval.unwrap_or(DEFAULT_INTERVAL),
+        loop_tour: loop_tour.unwrap_or(true),
+    };
+    if let Err(err) = write_tour_files(&work, &dir, &walkthrough) {
+        return Err(InvokeError::from(err));
+    }
+    Ok(walkthrough)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_walkthrough, tour_html, viewer_path, Walkthrough};
+    use crate::command::work::{ORIGIN_DIR, PREVIEW_DIR};
+    use std::fs;
+
+    #[test]
+    fn tour_html_loads_the_player() {
+        assert_eq!(
+            tour_html(b"<html><body><div></div></body></html>"),
+            "<html><body><div></div><script src=\"./tour.js\"></script>\n</body></html>"
+        );
+    }
+
+    #[test]
+    fn walkthrough_round_trip() {
+        let dir = std::env::temp_dir().join("tour_walkthrough_round_trip");
+        fs::create_dir_all(&dir).unwrap();
+        let walkthrough = Walkthrough {
+            path: vec![0, 2, 1],
+            distance: 4.5,
+            unreachable: vec![3],
+            interval: 1000,
+            loop_tour: false,
+        };
+        let content = serde_json::to_string(&walkthrough).unwrap();
+        assert!(content.contains("\"loop\":false"));
+        fs::write(dir.join("tour.json"), content).unwrap();
+        let read = read_walkthrough(dir.to_str().unwrap()).unwrap();
+        assert_eq!(read.path, vec![0, 2, 1]);
+        assert_eq!(read.unreachable, vec![3]);
+        assert!(!read.loop_tour);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tour_goes_next_to_the_viewer() {
+        let dir = std::env::temp_dir().join("tour_goes_next_to_the_viewer");
+        fs::create_dir_all(&dir).unwrap();
+        let dir_str = dir.to_str().unwrap();
+        fs::write(dir.join("selection.json"), "{\"panoramas\":null,\"floors\":null}").unwrap();
+        assert_eq!(viewer_path(dir_str), dir.join(PREVIEW_DIR));
+        fs::write(dir.join("selection.json"), "{\"panoramas\":null,\"floors\":null,\"raw\":true}").unwrap();
+        assert_eq!(viewer_path(dir_str), dir.join(ORIGIN_DIR));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
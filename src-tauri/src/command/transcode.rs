@@ -0,0 +1,177 @@
+use crate::command::redact::transcode_original;
+use crate::command::work::{
+    read_local_work, read_selection, read_work, with_jsonp_suffix, write_jsonp_from_origin,
+    write_preview_files, write_raw_files, write_selection, with_work_locked, Work, ORIGIN_DIR, PREVIEW_DIR,
+};
+use crate::util::transcode::{
+    format_from_name, TranscodeProfile, FORMAT_JPEG, FORMAT_PNG, FORMAT_WEBP,
+};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::{WebPEncoder, WebPQuality};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageEncoder};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tauri::InvokeError;
+use tokio;
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct TranscodeReport {
+    pub files: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+#[allow(deprecated)]
+pub fn encode(img: &DynamicImage, format: &str, quality: u8) -> Result<Vec<u8>, String> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let result = match format {
+        FORMAT_WEBP => {
+            let rgba = img.to_rgba8();
+            WebPEncoder::new_with_quality(&mut buffer, WebPQuality::lossy(quality)).encode(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                image::ColorType::Rgba8,
+            )
+        }
+        FORMAT_PNG => {
+            let rgba = img.to_rgba8();
+            PngEncoder::new(&mut buffer).write_image(
+                &rgba,
+                rgba.width(),
+                rgba.height(),
+                image::ColorType::Rgba8,
+            )
+        }
+        _ => JpegEncoder::new_with_quality(&mut buffer, quality).encode_image(&img.to_rgb8()),
+    };
+    match result {
+        Ok(_) => Ok(buffer),
+        Err(err) => Err(format!("encode {} error: {}", format, err)),
+    }
+}
+
+pub fn transcode_image(data: &[u8], format: &str, profile: &TranscodeProfile) -> Result<Vec<u8>, String> {
+    let img = image::load_from_memory(data);
+    if let Err(err) = img {
+        return Err(format!("decode image error: {}", err));
+    }
+    let mut img = img.unwrap();
+    if let Some(max_edge) = profile.max_edge {
+        if img.width().max(img.height()) > max_edge {
+            img = img.resize(max_edge, max_edge, FilterType::Lanczos3);
+        }
+    }
+    encode(&img, format, profile.quality)
+}
+
+fn face_names(work: &Work) -> HashSet<String> {
+    let mut names: HashSet<String> = HashSet::new();
+    for item in work.panorama.list.iter() {
+        for face in [&item.right, &item.left, &item.front, &item.back, &item.up, &item.down] {
+            names.insert(face.clone());
+        }
+    }
+    names
+}
+
+fn texture_names(work: &Work) -> HashSet<String> {
+    work.model
+        .material_textures
+        .iter()
+        .map(|name| format!("{}{}", work.model.material_base_url, name))
+        .collect()
+}
+
+// `current` is the work with the names its files have in `origin/` right now
+// `only` limits the work to files just downloaded, the others are transcoded already
+pub fn transcode_files(
+    dir: &str,
+    current: &Work,
+    profile: &TranscodeProfile,
+    only: Option<&HashSet<String>>,
+) -> Result<TranscodeReport, String> {
+    if profile.format != FORMAT_JPEG && profile.format != FORMAT_WEBP {
+        return Err(format!("unknown transcode format `{}`", profile.format));
+    }
+    let mut selection = read_selection(dir.to_string());
+    let target = read_work(dir.to_string())?.select(&selection).transcoded(profile);
+    let path = Path::new(dir);
+    let origin_path = path.join(ORIGIN_DIR);
+    let preview_path = path.join(PREVIEW_DIR);
+    let faces = face_names(current);
+    let textures = texture_names(current);
+    let mut report = TranscodeReport::default();
+
+    let current_list = current.get_download_list();
+    let target_list = target.get_download_list();
+    for (index, ((_, from_name), (_, to_name))) in current_list.iter().zip(target_list.iter()).enumerate() {
+        if only.map_or(false, |only| !only.contains(from_name)) {
+            continue;
+        }
+        let format = if faces.contains(from_name) && profile.faces {
+            profile.format.as_str()
+        } else if textures.contains(from_name) && profile.textures {
+            match format_from_name(from_name) {
+                Some(format) => format,
+                None => continue,
+            }
+        } else {
+            continue;
+        };
+        let data = fs::read(origin_path.join(from_name));
+        if let Err(err) = data {
+            return Err(format!("read `{}` error: {}", from_name, err));
+        }
+        let data = data.unwrap();
+        let output = transcode_image(&data, format, profile);
+        if let Err(err) = output {
+            return Err(format!("transcode `{}` error: {}", from_name, err));
+        }
+        let output = output.unwrap();
+        if let Err(err) = fs::write(origin_path.join(to_name), &output) {
+            return Err(format!("write `{}` error: {}", to_name, err));
+        }
+        if faces.contains(from_name) {
+            transcode_original(dir, from_name, to_name, &|data| transcode_image(data, format, profile))?;
+        }
+        if from_name != to_name {
+            _ = fs::remove_file(origin_path.join(from_name));
+            _ = fs::remove_file(with_jsonp_suffix(preview_path.join(from_name).to_str().unwrap(), index));
+        }
+        report.files = report.files + 1;
+        report.bytes_before = report.bytes_before + data.len() as u64;
+        report.bytes_after = report.bytes_after + output.len() as u64;
+    }
+
+    selection.transcode = Some(profile.clone());
+    write_selection(dir, &selection)?;
+    if selection.raw {
+        write_raw_files(&target, dir)?;
+    } else {
+        write_jsonp_from_origin(&target, dir)?;
+        write_preview_files(&target, dir)?;
+    }
+    Ok(report)
+}
+
+pub fn transcode_work(dir: &str, profile: &TranscodeProfile) -> Result<TranscodeReport, String> {
+    transcode_files(dir, &read_local_work(dir)?, profile, None)
+}
+
+#[tauri::command]
+pub async fn transcode_work_images(
+    dir: String,
+    profile: TranscodeProfile,
+) -> Result<TranscodeReport, InvokeError> {
+    let result = tokio::task::spawn_blocking(move || with_work_locked(&dir, || transcode_work(&dir, &profile))).await;
+    match result {
+        Ok(Ok(report)) => Ok(report),
+        Ok(Err(err)) => Err(InvokeError::from(err)),
+        Err(err) => Err(InvokeError::from(err.to_string())),
+    }
+}
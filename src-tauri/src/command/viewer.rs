@@ -0,0 +1,301 @@
+use crate::command::branding::{overlay_html, read_branding, viewer_file};
+use crate::command::graph::initial_pano;
+use crate::command::work::{
+    read_local_work, read_selection, with_work_locked, write_preview_files, write_raw_files, Asset, Work,
+};
+use crate::util::file;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, InvokeError};
+
+const VIEWER_FILE: &str = "viewer.json";
+const TEMPLATE_DIR: &str = "templates";
+pub const DEFAULT_TEMPLATE: &str = "default";
+const DEFAULT_TITLE: &str = "REALSEE PREVIEW";
+const DEFAULT_LANGUAGE: &str = "en";
+// files of a template that get their `{{variable}}`s replaced
+const TEXT_EXTENSIONS: [&str; 5] = [".html", ".htm", ".js", ".css", ".json"];
+
+lazy_static! {
+    // templates folder of the app data dir, downloads and rebuilds have no app handle
+    static ref TEMPLATES_ROOT: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+// viewer of a work, saved as viewer.json next to input.json
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ViewerConfig {
+    // name of a folder in the templates dir, looked up whenever the viewer is written
+    #[serde(default = "default_template")]
+    pub template: String,
+    pub title: Option<String>,
+    pub language: Option<String>,
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
+
+fn default_template() -> String {
+    String::from(DEFAULT_TEMPLATE)
+}
+
+impl Default for ViewerConfig {
+    fn default() -> ViewerConfig {
+        ViewerConfig {
+            template: default_template(),
+            title: None,
+            language: None,
+            variables: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ViewerTemplate {
+    pub name: String,
+    // None for the embedded template
+    pub dir: Option<String>,
+    pub files: Vec<String>,
+}
+
+pub fn read_viewer_config(dir: &str) -> ViewerConfig {
+    match fs::read_to_string(Path::new(dir).join(VIEWER_FILE)) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => ViewerConfig::default(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// one pass over `content`, substituted values are never looked at again
+fn substitute(content: &str, variables: &HashMap<String, String>) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        };
+        match variables.get(&after[..end]) {
+            Some(value) => result.push_str(value),
+            None => result.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn variables(work: &Work, dir: &str, config: &ViewerConfig) -> Result<HashMap<String, String>, String> {
+    let mut variables: HashMap<String, String> = config
+        .variables
+        .iter()
+        .map(|(name, value)| (name.clone(), escape_html(value)))
+        .collect();
+    let title = config.title.clone().unwrap_or(String::from(DEFAULT_TITLE));
+    variables.insert(String::from("title"), escape_html(&title));
+    let language = config.language.clone().unwrap_or(String::from(DEFAULT_LANGUAGE));
+    variables.insert(String::from("language"), escape_html(&language));
+    variables.insert(String::from("initial_pano"), initial_pano(work).to_string());
+    let branding = match read_branding(dir) {
+        Some(branding) => overlay_html(&branding)?,
+        None => String::new(),
+    };
+    variables.insert(String::from("branding"), branding);
+    Ok(variables)
+}
+
+fn is_text(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    TEXT_EXTENSIONS.iter().any(|ext| lower.ends_with(ext))
+}
+
+pub fn init_templates(app: &AppHandle) {
+    if let Ok(root) = templates_dir(app) {
+        *TEMPLATES_ROOT.lock().unwrap() = Some(root);
+    }
+}
+
+// folder of the template `name`, None when it was moved or deleted
+fn template_dir(name: &str) -> Option<String> {
+    let root = TEMPLATES_ROOT.lock().unwrap().clone()?;
+    let path = root.join(name);
+    if !path.join("index.html").is_file() {
+        return None;
+    }
+    path.to_str().map(|p| p.to_string())
+}
+
+// the embedded viewer overlaid with the files of the work's template, variables
+// substituted, branding placed at `{{branding}}` or appended to index.html
+pub fn viewer_files(work: &Work, dir: &str) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let config = read_viewer_config(dir);
+    let template_dir = if config.template == DEFAULT_TEMPLATE {
+        None
+    } else {
+        let found = template_dir(&config.template);
+        if found.is_none() {
+            println!("viewer template `{}` not found, using the embedded viewer", config.template);
+        }
+        found
+    };
+    let mut files: BTreeMap<String, (Vec<u8>, bool)> = BTreeMap::new();
+    for f in Asset::iter() {
+        let a = Asset::get(f.as_ref()).unwrap();
+        // only index.html of the embedded viewer has variables
+        files.insert(f.to_string(), (a.data.to_vec(), f.as_ref() == "index.html"));
+    }
+    if let Some(template_dir) = &template_dir {
+        for (name, _) in file::list_files(template_dir)? {
+            let data = fs::read(Path::new(template_dir).join(&name));
+            if let Err(err) = data {
+                return Err(format!("read template file `{}` error: {}", name, err));
+            }
+            let text = is_text(&name);
+            files.insert(name, (data.unwrap(), text));
+        }
+    }
+    let variables = variables(work, dir, &config)?;
+    let mut result: Vec<(String, Vec<u8>)> = Vec::new();
+    for (name, (data, text)) in files.into_iter() {
+        if !text {
+            result.push((name, data));
+            continue;
+        }
+        let content = String::from_utf8_lossy(&data).to_string();
+        let placed = content.contains("{{branding}}");
+        let data = substitute(&content, &variables).into_bytes();
+        if name == "index.html" && !placed {
+            let data = viewer_file(dir, &name, &data)?;
+            result.push((name, data));
+        } else {
+            result.push((name, data));
+        }
+    }
+    Ok(result)
+}
+
+pub fn write_viewer_files(work: &Work, dir: &str, dest: &Path) -> Result<(), String> {
+    for (name, data) in viewer_files(work, dir)? {
+        let path = dest.join(&name);
+        if let Some(parent) = path.parent() {
+            if let Err(err) = fs::create_dir_all(parent) {
+                return Err(err.to_string());
+            }
+        }
+        if let Err(err) = fs::write(&path, &data) {
+            return Err(format!("write static file `{}` error: {}", name, err));
+        }
+    }
+    Ok(())
+}
+
+fn templates_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    match app.path_resolver().app_data_dir() {
+        Some(dir) => Ok(dir.join(TEMPLATE_DIR)),
+        None => Err(String::from("app data dir not found")),
+    }
+}
+
+// every folder of the templates dir with an index.html is a template
+pub fn list_templates(app: &AppHandle) -> Result<Vec<ViewerTemplate>, String> {
+    let mut list = vec![ViewerTemplate {
+        name: String::from(DEFAULT_TEMPLATE),
+        dir: None,
+        files: Asset::iter().map(|f| f.to_string()).collect(),
+    }];
+    let root = templates_dir(app)?;
+    if let Err(err) = fs::create_dir_all(&root) {
+        return Err(format!("create `{}` error: {}", root.display(), err));
+    }
+    let entries = fs::read_dir(&root);
+    if let Err(err) = entries {
+        return Err(err.to_string());
+    }
+    let mut templates: Vec<ViewerTemplate> = Vec::new();
+    for entry in entries.unwrap().flatten() {
+        let path = entry.path();
+        if !path.join("index.html").is_file() {
+            continue;
+        }
+        let dir = path.to_str().unwrap().to_string();
+        templates.push(ViewerTemplate {
+            name: entry.file_name().to_string_lossy().to_string(),
+            files: file::list_files(&dir)?.into_iter().map(|f| f.0).collect(),
+            dir: Some(dir),
+        });
+    }
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    list.extend(templates);
+    Ok(list)
+}
+
+// save the viewer config of a work and write its viewer files again
+pub fn set_viewer(app: &AppHandle, dir: &str, config: ViewerConfig) -> Result<(), String> {
+    if !list_templates(app)?.iter().any(|t| t.name == config.template) {
+        return Err(format!("viewer template `{}` not found", config.template));
+    }
+    if let Err(err) = fs::write(
+        Path::new(dir).join(VIEWER_FILE),
+        serde_json::to_string_pretty(&config).unwrap().as_bytes(),
+    ) {
+        return Err(format!("write {} error: {}", VIEWER_FILE, err));
+    }
+    let work = read_local_work(dir)?;
+    if read_selection(dir.to_string()).raw {
+        return write_raw_files(&work, dir);
+    }
+    write_preview_files(&work, dir)
+}
+
+#[tauri::command]
+pub fn list_viewer_templates(app: AppHandle) -> Result<Vec<ViewerTemplate>, InvokeError> {
+    match list_templates(&app) {
+        Ok(list) => Ok(list),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[tauri::command]
+pub fn set_work_viewer(app: AppHandle, dir: String, config: ViewerConfig) -> Result<String, InvokeError> {
+    match with_work_locked(&dir, || set_viewer(&app, &dir, config)) {
+        Ok(_) => Ok(String::from("ok")),
+        Err(err) => Err(InvokeError::from(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::substitute;
+    use std::collections::HashMap;
+
+    #[test]
+    fn substitute_once() {
+        let mut variables: HashMap<String, String> = HashMap::new();
+        variables.insert(String::from("title"), String::from("{{language}}"));
+        variables.insert(String::from("language"), String::from("en"));
+        assert_eq!(
+            substitute("<title>{{title}}</title><html lang=\"{{language}}\">", &variables),
+            "<title>{{language}}</title><html lang=\"en\">"
+        );
+    }
+
+    #[test]
+    fn substitute_keeps_unknown() {
+        let mut variables: HashMap<String, String> = HashMap::new();
+        variables.insert(String::from("a"), String::from("1"));
+        assert_eq!(substitute("{{a}}{{b}}{{a", &variables), "1{{b}}{{a");
+        assert_eq!(substitute("x{{}}y", &variables), "x{{}}y");
+    }
+}
@@ -1,18 +1,27 @@
+use crate::command::branding::{write_branded_faces, write_raw_branded_faces};
+use crate::command::library::{fetch_source_title, finish_download, register_download};
+use crate::command::redact::{apply_redactions, forget_originals, read_redactions};
+use crate::command::thumbnail::{generate_thumbnails, DEFAULT_THUMBNAIL_SIZE, MODE_FRONT};
+use crate::command::transcode::transcode_files;
+use crate::command::viewer::write_viewer_files;
 use crate::util::file;
+use crate::util::mime::{is_mime_token, mime_type, sniff_mime_type};
+use crate::util::transcode::{transcoded_name, TranscodeProfile};
 use base64::{engine::general_purpose, Engine as _};
 use reqwest;
 use rust_embed::RustEmbed;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use tar::Archive;
+use tauri::AppHandle;
 use tokio;
 
 #[derive(RustEmbed)]
 #[folder = "static/"]
-struct Asset;
+pub struct Asset;
 
 #[warn(dead_code)]
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -27,14 +36,24 @@ lazy_static! {
     pub static ref TASK_STATE: Arc<Mutex<HashMap<String, TaskState>>> =
         Arc::new(Mutex::new(HashMap::new()));
     pub static ref RUNNING: Arc<Mutex<usize>> = Arc::new(Mutex::new(0));
+    // work dirs queued, downloading or syncing, one job per dir at a time
+    pub static ref BUSY: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
 }
 
 const IMAGE_JPEG: &str = "image/jpeg";
 const IMAGE_JPG: &str = "image/jpg";
-const IMAGE_PNG: &str = "image/png";
 
-const PREVIEW_DIR: &str = "preview";
-const ORIGIN_DIR: &str = "origin";
+// what servers send for files they know nothing about
+const GENERIC_TYPES: [&str; 4] = [
+    "application/octet-stream",
+    "binary/octet-stream",
+    "application/binary",
+    "application/unknown",
+];
+const MANIFEST_FILE: &str = "manifest.json";
+
+pub const PREVIEW_DIR: &str = "preview";
+pub const ORIGIN_DIR: &str = "origin";
 
 fn get_task() -> Option<String> {
     let mut list = TASK_LIST.lock().unwrap();
@@ -65,6 +84,25 @@ fn set_running(flag: usize) {
     *running = flag;
 }
 
+pub fn lock_work(dir: &str) -> Result<(), String> {
+    if !BUSY.lock().unwrap().insert(dir.to_string()) {
+        return Err(format!("`{}` is busy, a download, sync or edit is running on it", dir));
+    }
+    Ok(())
+}
+
+pub fn unlock_work(dir: &str) {
+    BUSY.lock().unwrap().remove(dir);
+}
+
+// run a change of the files of a work, unless a download or sync is on it
+pub fn with_work_locked<T>(dir: &str, change: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    lock_work(dir)?;
+    let result = change();
+    unlock_work(dir);
+    result
+}
+
 // Example code that deserializes and serializes the model.
 // extern crate serde;
 // #[macro_use]
@@ -80,72 +118,170 @@ fn set_running(flag: usize) {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Work {
-    base_url: String,
-    initial: Initial,
-    model: Model,
-    observers: Vec<Observer>,
-    panorama: Panorama,
-    picture_url: String,
-    title_picture_url: String,
+    pub base_url: String,
+    pub initial: Initial,
+    pub model: Model,
+    pub observers: Vec<Observer>,
+    pub panorama: Panorama,
+    pub picture_url: String,
+    pub title_picture_url: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Initial {
-    flag_position: Option<Vec<serde_json::Value>>,
-    fov: i64,
-    heading: Option<i64>,
-    latitude: f64,
-    longitude: f64,
-    pano: Option<i64>,
-    pano_index: Option<i64>,
+    pub flag_position: Option<Vec<serde_json::Value>>,
+    pub fov: i64,
+    pub heading: Option<i64>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub pano: Option<i64>,
+    pub pano_index: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Model {
-    file_url: String,
-    material_base_url: String,
-    material_textures: Vec<String>,
+    pub file_url: String,
+    pub material_base_url: String,
+    pub material_textures: Vec<String>,
     #[serde(rename = "type")]
-    model_type: i64,
+    pub model_type: i64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Observer {
-    accessible_nodes: Vec<i64>,
-    floor_index: i64,
-    index: i64,
-    offset_point_count: i64,
-    position: Vec<f64>,
-    quaternion: Quaternion,
-    standing_position: Vec<f64>,
-    visible_nodes: Vec<i64>,
+    pub accessible_nodes: Vec<i64>,
+    pub floor_index: i64,
+    pub index: i64,
+    pub offset_point_count: i64,
+    pub position: Vec<f64>,
+    pub quaternion: Quaternion,
+    pub standing_position: Vec<f64>,
+    pub visible_nodes: Vec<i64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Quaternion {
-    w: f64,
-    x: f64,
-    y: f64,
-    z: f64,
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Panorama {
-    count: i64,
-    list: Vec<PanoramaItem>,
+    pub count: i64,
+    pub list: Vec<PanoramaItem>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PanoramaItem {
-    back: String,
-    derived_id: Option<i64>,
-    down: String,
-    front: String,
-    index: i64,
-    left: String,
-    right: String,
-    tiles: Option<Vec<i64>>,
-    up: String,
+    pub back: String,
+    pub derived_id: Option<i64>,
+    pub down: String,
+    pub front: String,
+    pub index: i64,
+    pub left: String,
+    pub right: String,
+    pub tiles: Option<Vec<i64>>,
+    pub up: String,
+}
+
+// which parts of a work `add_work_download_task` fetches, everything by default
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Selection {
+    pub panoramas: Option<Vec<i64>>,
+    pub floors: Option<Vec<i64>>,
+    #[serde(default = "default_true")]
+    pub model: bool,
+    #[serde(default = "default_true")]
+    pub textures: bool,
+    #[serde(default = "default_true")]
+    pub src_model: bool,
+    #[serde(default = "default_true")]
+    pub src_pano: bool,
+    #[serde(default = "default_true")]
+    pub pictures: bool,
+    // only raw assets in `origin/` with a plain work.json, no jsonp `preview/`
+    #[serde(default)]
+    pub raw: bool,
+    // re-encode faces and textures once downloaded
+    pub transcode: Option<TranscodeProfile>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for Selection {
+    fn default() -> Selection {
+        Selection {
+            panoramas: None,
+            floors: None,
+            model: true,
+            textures: true,
+            src_model: true,
+            src_pano: true,
+            pictures: true,
+            raw: false,
+            transcode: None,
+        }
+    }
+}
+
+impl Quaternion {
+    pub fn from_axis_angle(axis: [f64; 3], angle: f64) -> Quaternion {
+        let half = angle / 2.0;
+        Quaternion {
+            w: half.cos(),
+            x: axis[0] * half.sin(),
+            y: axis[1] * half.sin(),
+            z: axis[2] * half.sin(),
+        }
+    }
+    pub fn multiply(&self, other: &Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        }
+    }
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+    pub fn rotate(&self, v: &[f64]) -> Vec<f64> {
+        let p = Quaternion {
+            w: 0.0,
+            x: v[0],
+            y: v[1],
+            z: v[2],
+        };
+        let r = self.multiply(&p).multiply(&self.conjugate());
+        vec![r.x, r.y, r.z]
+    }
+}
+
+impl PanoramaItem {
+    // faces laid out as `<dir>/<face>.jpg`, relative to the work's base_url
+    pub fn from_face_dir(index: i64, dir: &str) -> PanoramaItem {
+        let face = |name: &str| format!("{}/{}.jpg", dir.trim_end_matches('/'), name);
+        PanoramaItem {
+            back: face("back"),
+            derived_id: None,
+            down: face("down"),
+            front: face("front"),
+            index: index,
+            left: face("left"),
+            right: face("right"),
+            tiles: None,
+            up: face("up"),
+        }
+    }
 }
 
 impl Work {
@@ -159,13 +295,18 @@ impl Work {
         full_url.push_str(suffix);
         return full_url;
     }
-    fn get_download_list(&self) -> Vec<(String, String)> {
+    // assets left blank by a `Selection` are not downloaded
+    pub fn get_download_list(&self) -> Vec<(String, String)> {
         let mut download: Vec<(String, String)> = Vec::new();
-        download.push((self.picture_url.clone(), String::from("picture.jpg")));
-        download.push((
-            self.title_picture_url.clone(),
-            String::from("title_picture.jpg"),
-        ));
+        if !self.picture_url.is_empty() {
+            download.push((self.picture_url.clone(), String::from("picture.jpg")));
+        }
+        if !self.title_picture_url.is_empty() {
+            download.push((
+                self.title_picture_url.clone(),
+                String::from("title_picture.jpg"),
+            ));
+        }
         for item in self.panorama.list.iter() {
             download.push((self.with_base_url(&item.right), item.right.clone()));
             download.push((self.with_base_url(&item.left), item.left.clone()));
@@ -174,10 +315,12 @@ impl Work {
             download.push((self.with_base_url(&item.up), item.up.clone()));
             download.push((self.with_base_url(&item.down), item.down.clone()));
         }
-        download.push((
-            self.with_base_url(&self.model.file_url),
-            self.model.file_url.clone(),
-        ));
+        if !self.model.file_url.is_empty() {
+            download.push((
+                self.with_base_url(&self.model.file_url),
+                self.model.file_url.clone(),
+            ));
+        }
         for item in self.model.material_textures.iter() {
             download.push((
                 self.with_base_url(&self.with_model_base_url(&item)),
@@ -186,51 +329,190 @@ impl Work {
         }
         return download;
     }
-    fn get_jsonp_work(&self) -> String {
+    pub fn select(&self, selection: &Selection) -> Work {
         let mut work = self.clone();
+        if selection.panoramas.is_some() || selection.floors.is_some() {
+            let keep: Vec<i64> = self
+                .observers
+                .iter()
+                .filter(|o| selection.panoramas.as_ref().map_or(true, |list| list.contains(&o.index)))
+                .filter(|o| selection.floors.as_ref().map_or(true, |list| list.contains(&o.floor_index)))
+                .map(|o| o.index)
+                .collect();
+            work = work.subset(&keep);
+        }
+        if !selection.model {
+            work.model.file_url = String::new();
+        }
+        if !selection.model || !selection.textures {
+            work.model.material_textures = Vec::new();
+        }
+        if !selection.pictures {
+            work.picture_url = String::new();
+            work.title_picture_url = String::new();
+        }
+        work
+    }
+    // keep the given observers only, renumbered from 0 in work order
+    pub fn subset(&self, keep: &Vec<i64>) -> Work {
+        let mut index_map: HashMap<i64, i64> = HashMap::new();
+        for observer in self.observers.iter() {
+            if keep.contains(&observer.index) {
+                index_map.insert(observer.index, index_map.len() as i64);
+            }
+        }
+        let remap = |nodes: &Vec<i64>| -> Vec<i64> {
+            nodes.iter().filter_map(|node| index_map.get(node).cloned()).collect()
+        };
+        let mut work = self.clone();
+        work.observers = Vec::new();
+        for observer in self.observers.iter() {
+            if let Some(index) = index_map.get(&observer.index) {
+                let mut observer = observer.clone();
+                observer.index = *index;
+                observer.accessible_nodes = remap(&observer.accessible_nodes);
+                observer.visible_nodes = remap(&observer.visible_nodes);
+                work.observers.push(observer);
+            }
+        }
+        work.panorama.list = Vec::new();
+        for item in self.panorama.list.iter() {
+            if let Some(index) = index_map.get(&item.index) {
+                let mut item = item.clone();
+                item.index = *index;
+                work.panorama.list.push(item);
+            }
+        }
+        work.panorama.count = work.panorama.list.len() as i64;
+        let initial = self.initial.pano_index.or(self.initial.pano);
+        let initial = initial.and_then(|index| index_map.get(&index).cloned());
+        if initial.is_none() {
+            work.initial.heading = None;
+            work.initial.flag_position = None;
+        }
+        work.initial.pano_index = Some(initial.unwrap_or(0));
+        work.initial.pano = work.initial.pano_index;
+        work
+    }
+    // face names after a transcode, textures keep theirs as the model refers to them
+    pub fn transcoded(&self, profile: &TranscodeProfile) -> Work {
+        let mut work = self.clone();
+        if !profile.faces {
+            return work;
+        }
+        for item in work.panorama.list.iter_mut() {
+            item.right = transcoded_name(&item.right, &profile.format);
+            item.left = transcoded_name(&item.left, &profile.format);
+            item.front = transcoded_name(&item.front, &profile.format);
+            item.back = transcoded_name(&item.back, &profile.format);
+            item.up = transcoded_name(&item.up, &profile.format);
+            item.down = transcoded_name(&item.down, &profile.format);
+        }
+        work
+    }
+    pub fn get_jsonp_work(&self) -> String {
+        let mut work = self.clone();
+        // same order as `get_download_list`, blank assets are skipped there
         let mut index: usize = 0;
-        work.picture_url = with_jsonp_suffix("picture.jpg", index);
-        index = index + 1;
-        work.title_picture_url = with_jsonp_suffix("title_picture.jpg", index);
-        for x in 0..self.panorama.list.len() {
-            index = index + 1;
-            work.panorama.list[x].right = with_jsonp_suffix(&work.panorama.list[x].right, index);
+        let mut jsonp = |name: &str| -> String {
+            if name.is_empty() {
+                return String::new();
+            }
             index = index + 1;
-            work.panorama.list[x].left = with_jsonp_suffix(&work.panorama.list[x].left, index);
-            index = index + 1;
-            work.panorama.list[x].front = with_jsonp_suffix(&work.panorama.list[x].front, index);
-            index = index + 1;
-            work.panorama.list[x].back = with_jsonp_suffix(&work.panorama.list[x].back, index);
-            index = index + 1;
-            work.panorama.list[x].up = with_jsonp_suffix(&work.panorama.list[x].up, index);
-            index = index + 1;
-            work.panorama.list[x].down = with_jsonp_suffix(&work.panorama.list[x].down, index);
+            with_jsonp_suffix(name, index - 1)
+        };
+        if !work.picture_url.is_empty() {
+            work.picture_url = jsonp("picture.jpg");
+        }
+        if !work.title_picture_url.is_empty() {
+            work.title_picture_url = jsonp("title_picture.jpg");
         }
-        index = index + 1;
-        work.model.file_url = with_jsonp_suffix(&work.model.file_url, index);
+        for item in work.panorama.list.iter_mut() {
+            item.right = jsonp(&item.right);
+            item.left = jsonp(&item.left);
+            item.front = jsonp(&item.front);
+            item.back = jsonp(&item.back);
+            item.up = jsonp(&item.up);
+            item.down = jsonp(&item.down);
+        }
+        work.model.file_url = jsonp(&work.model.file_url);
         for x in 0..work.model.material_textures.len() {
-            index = index + 1;
-            work.model.material_textures[x] =
-                with_jsonp_suffix(&work.model.material_textures[x], index);
+            work.model.material_textures[x] = jsonp(&work.model.material_textures[x]);
+        }
+        serde_json::to_string(&work).unwrap()
+    }
+    // assets keep their `origin/` names, loaded over http next to index.html
+    fn get_raw_work(&self) -> String {
+        let mut work = self.clone();
+        if !work.picture_url.is_empty() {
+            work.picture_url = String::from("picture.jpg");
+        }
+        if !work.title_picture_url.is_empty() {
+            work.title_picture_url = String::from("title_picture.jpg");
         }
         serde_json::to_string(&work).unwrap()
     }
 }
 
-pub async fn download_work_to(work: &Work, dir: String) -> Result<(), String> {
+// one downloaded file, `content_type` is the type of its jsonp data uri
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManifestEntry {
+    pub index: usize,
+    pub name: String,
+    pub content_type: String,
+    // None when the file was read from `origin/` instead of downloaded
+    pub header_content_type: Option<String>,
+    pub size: usize,
+}
+
+pub fn read_manifest(dir: &str) -> Vec<ManifestEntry> {
+    match fs::read_to_string(Path::new(dir).join(MANIFEST_FILE)) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn write_manifest(dir: &str, manifest: &Vec<ManifestEntry>) -> Result<(), String> {
+    if let Err(err) = fs::write(
+        Path::new(dir).join(MANIFEST_FILE),
+        serde_json::to_string_pretty(manifest).unwrap().as_bytes(),
+    ) {
+        return Err(format!("write {} error: {}", MANIFEST_FILE, err));
+    }
+    Ok(())
+}
+
+pub async fn download_work_to(
+    work: &Work,
+    dir: String,
+    selection: &Selection,
+) -> Result<(), String> {
     let download: Vec<(String, String)> = work.get_download_list();
     let path = Path::new(&dir);
     let total = download.len() + 2;
     let preview_path = path.join(PREVIEW_DIR);
     let origin_path = path.join(ORIGIN_DIR);
+    let mut manifest: Vec<ManifestEntry> = Vec::new();
     for (index, item) in download.iter().enumerate() {
-        download_file(
+        let jsonp_dest = if selection.raw {
+            None
+        } else {
+            Some(with_jsonp_suffix(preview_path.join(item.1.clone()).to_str().unwrap(), index))
+        };
+        let (content_type, header_content_type, size) = download_file(
             item.0.clone(),
             origin_path.join(item.1.clone()).to_str().unwrap(),
-            &with_jsonp_suffix(preview_path.join(item.1.clone()).to_str().unwrap(), index),
+            jsonp_dest.as_deref(),
             index,
         )
         .await?;
+        manifest.push(ManifestEntry {
+            index,
+            name: item.1.clone(),
+            content_type,
+            header_content_type,
+            size,
+        });
 
         update_task(
             dir.clone(),
@@ -242,21 +524,38 @@ pub async fn download_work_to(work: &Work, dir: String) -> Result<(), String> {
         );
     }
 
-    if let Err(err) = download_src_model(
-        &work.with_base_url(&"src_model.tar"),
-        origin_path.to_str().unwrap(),
-    )
-    .await
-    {
-        println!("download_src_model error {}", err);
+    write_manifest(&dir, &manifest)?;
+
+    if selection.src_model {
+        if let Err(err) = download_src_model(
+            &work.with_base_url(&"src_model.tar"),
+            origin_path.to_str().unwrap(),
+        )
+        .await
+        {
+            println!("download_src_model error {}", err);
+        }
     }
 
-    _ = download_src_pano(
-        &work.with_base_url(&"src_pano.tar"),
-        origin_path.to_str().unwrap(),
-    )
-    .await;
+    if selection.src_pano {
+        _ = download_src_pano(
+            &work.with_base_url(&"src_pano.tar"),
+            origin_path.to_str().unwrap(),
+        )
+        .await;
+    }
 
+    if selection.raw {
+        return write_raw_files(work, &dir);
+    }
+    write_preview_files(work, &dir)
+}
+
+// write work.js, work.json and the embedded viewer files next to the assets
+pub fn write_preview_files(work: &Work, dir: &str) -> Result<(), String> {
+    let path = Path::new(dir);
+    let preview_path = path.join(PREVIEW_DIR);
+    let origin_path = path.join(ORIGIN_DIR);
     let work_json = work.get_jsonp_work();
     let work_json_content = format!("var workJSON = {}", work_json);
 
@@ -276,25 +575,63 @@ pub async fn download_work_to(work: &Work, dir: String) -> Result<(), String> {
     }
 
     // write some static js and html files to preview directory
-    for f in Asset::iter() {
-        let a = Asset::get(f.as_ref()).unwrap();
-        if let Err(err) = fs::write(preview_path.join(f.as_ref()), a.data.as_ref()) {
-            return Err(format!(
-                "write static file `{}` error: {}",
-                f.as_ref(),
-                err.to_string()
-            ));
-        }
+    write_viewer_files(work, dir, &preview_path)?;
+    write_branded_faces(work, dir)
+}
+
+// write work.json, work.js and the viewer files into `origin/` for the raw layout
+pub fn write_raw_files(work: &Work, dir: &str) -> Result<(), String> {
+    let origin_path = Path::new(dir).join(ORIGIN_DIR);
+    let work = &write_raw_branded_faces(work, dir)?;
+    let work_json = work.get_raw_work();
+    if let Err(err) = fs::write(origin_path.join("work.json"), work_json.as_bytes()) {
+        return Err(format!("write work.json error {}", err));
     }
+    let work_js = format!("var workJSON = {}", work_json);
+    if let Err(err) = fs::write(origin_path.join("work.js"), work_js.as_bytes()) {
+        return Err(format!("write work.js error {}", err));
+    }
+    write_viewer_files(work, dir, &origin_path)
+}
 
-    Ok(())
+// generate the jsonp files of a work whose assets are already in `origin/`
+pub fn write_jsonp_from_origin(work: &Work, dir: &str) -> Result<(), String> {
+    let path = Path::new(dir);
+    let preview_path = path.join(PREVIEW_DIR);
+    let origin_path = path.join(ORIGIN_DIR);
+    // keep what the server sent when the work was downloaded
+    let headers: HashMap<String, Option<String>> = read_manifest(dir)
+        .into_iter()
+        .map(|entry| (entry.name, entry.header_content_type))
+        .collect();
+    let mut manifest: Vec<ManifestEntry> = Vec::new();
+    for (index, item) in work.get_download_list().iter().enumerate() {
+        let content = fs::read(origin_path.join(&item.1));
+        if let Err(err) = content {
+            return Err(format!("read `{}` error: {}", item.1, err));
+        }
+        let content = content.unwrap();
+        let header_content_type = headers.get(&item.1).cloned().flatten();
+        let content_type = detect_content_type(&content, header_content_type.as_deref().unwrap_or(""), &item.1);
+        let jsonp_dest = with_jsonp_suffix(preview_path.join(&item.1).to_str().unwrap(), index);
+        create_file_directory(&jsonp_dest)?;
+        write_jsonp_file(&content_type, &content, &jsonp_dest, index)?;
+        manifest.push(ManifestEntry {
+            index,
+            name: item.1.clone(),
+            content_type,
+            header_content_type,
+            size: content.len(),
+        });
+    }
+    write_manifest(dir, &manifest)
 }
 
-fn with_jsonp_suffix(file_name: &str, hash_code: usize) -> String {
+pub fn with_jsonp_suffix(file_name: &str, hash_code: usize) -> String {
     return format!("{}.{}.jsonp", file_name, hash_code);
 }
 
-fn create_file_directory(dest: &str) -> Result<(), String> {
+pub fn create_file_directory(dest: &str) -> Result<(), String> {
     let path: &Path = Path::new(dest);
     if let Err(err) = std::fs::create_dir_all(path.parent().unwrap()) {
         return Err(err.to_string());
@@ -349,12 +686,13 @@ async fn download_src_pano(url: &str, dir: &str) -> Result<(), String> {
     Ok(())
 }
 
-async fn download_file(
+// returns the detected content type, the `Content-Type` header and the size
+pub async fn download_file(
     url: String,
     dest: &str,
-    jsonp_dest: &str,
+    jsonp_dest: Option<&str>,
     jsonp_hash_code: usize,
-) -> Result<(), String> {
+) -> Result<(String, Option<String>, usize), String> {
     if let Err(err) = create_file_directory(dest) {
         return Err(format!(
             "create file directory `{}` failed: {}",
@@ -362,12 +700,14 @@ async fn download_file(
             err.as_str()
         ));
     }
-    if let Err(err) = create_file_directory(jsonp_dest) {
-        return Err(format!(
-            "create file directory `{}` failed: {}",
-            jsonp_dest,
-            err.as_str()
-        ));
+    if let Some(jsonp_dest) = jsonp_dest {
+        if let Err(err) = create_file_directory(jsonp_dest) {
+            return Err(format!(
+                "create file directory `{}` failed: {}",
+                jsonp_dest,
+                err.as_str()
+            ));
+        }
     }
 
     //let resp = reqwest::blocking::get(url);
@@ -379,48 +719,93 @@ async fn download_file(
         return Err(err.to_string());
     }
     let response = result.unwrap();
-    let mut content_type = "";
+    let mut header_content_type: Option<String> = None;
     let header = response.headers().clone();
     if let Some(val) = header.get("Content-Type") {
-        content_type = val.to_str().unwrap();
+        header_content_type = val.to_str().ok().map(|val| val.to_string());
     }
     let bytes = response.bytes().await;
     let content = bytes.unwrap().as_ref().clone().to_vec();
-    let base64_data = generate_jsonp_content(&content_type, &content, jsonp_hash_code);
+    let content_type = detect_content_type(&content, header_content_type.as_deref().unwrap_or(""), dest);
+    match jsonp_dest {
+        Some(jsonp_dest) => save_file_content(&content_type, &content, dest, jsonp_dest, jsonp_hash_code)?,
+        None => {
+            if let Err(err) = fs::write(dest, &content) {
+                return Err(err.to_string());
+            }
+        }
+    }
+    Ok((content_type, header_content_type, content.len()))
+}
+
+// write the raw file to `dest` and its jsonp wrapper to `jsonp_dest`
+pub fn save_file_content(
+    content_type: &str,
+    content: &[u8],
+    dest: &str,
+    jsonp_dest: &str,
+    jsonp_hash_code: usize,
+) -> Result<(), String> {
     if let Err(err) = fs::write(dest, &content) {
         return Err(err.to_string());
     }
+    write_jsonp_file(content_type, content, jsonp_dest, jsonp_hash_code)
+}
+
+pub fn write_jsonp_file(
+    content_type: &str,
+    content: &[u8],
+    jsonp_dest: &str,
+    jsonp_hash_code: usize,
+) -> Result<(), String> {
+    let base64_data = generate_jsonp_content(&content_type, &content, jsonp_hash_code);
     if let Err(err) = fs::write(jsonp_dest, &base64_data.as_bytes()) {
         return Err(err.to_string());
     }
     Ok(())
 }
 
-fn generate_jsonp_content(content_type: &str, input: &[u8], hash_code: usize) -> String {
-    match content_type {
-        IMAGE_JPEG | IMAGE_JPG => format!(
-            "window[\"jsonp_{}\"] && window[\"jsonp_{}\"](\"data:image/jpeg;base64,{}\")",
-            hash_code,
-            hash_code,
-            general_purpose::STANDARD.encode(input)
-        ),
-        IMAGE_PNG => format!(
-            "window[\"jsonp_{}\"] && window[\"jsonp_{}\"](\"data:image/png;base64,{}\")",
-            hash_code,
-            hash_code,
-            general_purpose::STANDARD.encode(input)
-        ),
-        _ => format!(
-            "window['jsonp_{}'] && window['jsonp_{}'](\"data:application/octet-stream;base64,{}\")",
-            hash_code,
-            hash_code,
-            general_purpose::STANDARD.encode(input)
-        ),
+// the bytes win over the header, CDNs send `binary/octet-stream` for images,
+// the file name is the last resort, headers that are no plain `type/subtype` are ignored
+pub fn detect_content_type(content: &[u8], header: &str, file_name: &str) -> String {
+    if let Some(content_type) = sniff_mime_type(content) {
+        return content_type.to_string();
     }
+    let header = header.split(';').next().unwrap_or("").trim().to_lowercase();
+    if header == IMAGE_JPG {
+        return IMAGE_JPEG.to_string();
+    }
+    if is_mime_token(&header) && !GENERIC_TYPES.contains(&header.as_str()) {
+        return header;
+    }
+    mime_type(file_name).split(';').next().unwrap().to_string()
+}
+
+fn generate_jsonp_content(content_type: &str, input: &[u8], hash_code: usize) -> String {
+    let content_type = match content_type {
+        IMAGE_JPG => IMAGE_JPEG,
+        // the type ends up inside a js string
+        content_type if !is_mime_token(content_type) => GENERIC_TYPES[0],
+        content_type => content_type,
+    };
+    format!(
+        "window[\"jsonp_{}\"] && window[\"jsonp_{}\"](\"data:{};base64,{}\")",
+        hash_code,
+        hash_code,
+        content_type,
+        general_purpose::STANDARD.encode(input)
+    )
 }
 
 #[tauri::command]
-pub async fn add_work_download_task(dir: String, work_json: String) -> TaskState {
+pub async fn add_work_download_task(
+    app: AppHandle,
+    dir: String,
+    work_json: String,
+    selection: Option<Selection>,
+    source: Option<String>,
+    name: Option<String>,
+) -> TaskState {
     if let Err(err) = fs::create_dir_all(String::from(dir.clone())) {
         return TaskState {
             message: err.to_string(),
@@ -436,12 +821,21 @@ pub async fn add_work_download_task(dir: String, work_json: String) -> TaskState
             percent: 0,
         };
     }
+    // a sync or an earlier download of the dir would race on its files
+    if let Err(err) = lock_work(&dir) {
+        return TaskState {
+            message: err,
+            state: "failure".to_string(),
+            percent: 0,
+        };
+    }
 
     let path = Path::new(&dir);
     if let Err(err) = fs::write(
         path.join(&"input.json").to_str().unwrap(),
         work_json.as_bytes(),
     ) {
+        unlock_work(&dir);
         return TaskState {
             message: format!("write work input.json error:{}", err.to_string()),
             state: "failure".to_string(),
@@ -449,6 +843,28 @@ pub async fn add_work_download_task(dir: String, work_json: String) -> TaskState
         };
     }
 
+    let selection = selection.unwrap_or_default();
+    if let Err(err) = fs::write(
+        path.join(&"selection.json").to_str().unwrap(),
+        serde_json::to_string(&selection).unwrap().as_bytes(),
+    ) {
+        unlock_work(&dir);
+        return TaskState {
+            message: format!("write work selection.json error:{}", err.to_string()),
+            state: "failure".to_string(),
+            percent: 0,
+        };
+    }
+
+    // the catalog is a convenience, never refuse the download for it
+    let task_app = app.clone();
+    let task_dir = dir.clone();
+    match tokio::task::spawn_blocking(move || register_download(&task_app, &task_dir, source, name)).await {
+        Ok(Err(err)) => println!("register_download error {}", err),
+        Err(err) => println!("register_download error {}", err),
+        Ok(Ok(_)) => {}
+    }
+
     add_task(dir.clone());
     update_task(
         dir.clone(),
@@ -459,8 +875,9 @@ pub async fn add_work_download_task(dir: String, work_json: String) -> TaskState
         },
     );
 
+    tokio::spawn(fetch_source_title(app.clone(), dir.clone()));
     if !is_running() {
-        tokio::spawn(download_work_from_task_list());
+        tokio::spawn(download_work_from_task_list(app));
     }
 
     return TaskState {
@@ -494,12 +911,46 @@ pub async fn exec_download_work(dir: String, work_json: String) -> String {
 }
 */
 
-fn read_work(dir: String) -> Result<Work, serde_json::Error> {
-    let buffer = File::open(Path::new(&dir).join(&"input.json").to_str().unwrap()).unwrap();
-    serde_json::from_reader(buffer)
+pub fn read_work(dir: String) -> Result<Work, String> {
+    let buffer = File::open(Path::new(&dir).join(&"input.json").to_str().unwrap());
+    if let Err(err) = buffer {
+        return Err(format!("open input.json error:{}", err.to_string()));
+    }
+    match serde_json::from_reader(buffer.unwrap()) {
+        Ok(work) => Ok(work),
+        Err(err) => Err(format!("json_decode_work error:{}", err.to_string())),
+    }
+}
+
+// works queued before selections existed have no selection.json
+pub fn read_selection(dir: String) -> Selection {
+    match File::open(Path::new(&dir).join(&"selection.json").to_str().unwrap()) {
+        Ok(buffer) => serde_json::from_reader(buffer).unwrap_or_default(),
+        Err(_) => Selection::default(),
+    }
+}
+
+pub fn write_selection(dir: &str, selection: &Selection) -> Result<(), String> {
+    if let Err(err) = fs::write(
+        Path::new(dir).join("selection.json"),
+        serde_json::to_string(selection).unwrap().as_bytes(),
+    ) {
+        return Err(format!("write work selection.json error:{}", err));
+    }
+    Ok(())
+}
+
+// the work as it is stored on disk: selected parts, transcoded face names
+pub fn read_local_work(dir: &str) -> Result<Work, String> {
+    let selection = read_selection(dir.to_string());
+    let work = read_work(dir.to_string())?.select(&selection);
+    match &selection.transcode {
+        Some(profile) => Ok(work.transcoded(profile)),
+        None => Ok(work),
+    }
 }
 
-async fn download_work_from_task_list() -> Result<String, String> {
+async fn download_work_from_task_list(app: AppHandle) -> Result<String, String> {
     set_running(1);
     loop {
         let task_result = get_task();
@@ -509,8 +960,24 @@ async fn download_work_from_task_list() -> Result<String, String> {
         let dir = task_result.unwrap();
         println!("get task dir = {}", dir);
         let work = read_work(dir.clone());
-        if work.is_err() {
-            return Err(work.unwrap_err().to_string());
+        if let Err(err) = work {
+            // fail this task only, the rest of the queue still runs
+            let (task_app, task_dir) = (app.clone(), dir.clone());
+            match tokio::task::spawn_blocking(move || finish_download(&task_app, &task_dir, false)).await {
+                Ok(Err(err)) => println!("finish_download error {}", err),
+                Err(err) => println!("finish_download error {}", err),
+                Ok(Ok(_)) => {}
+            }
+            unlock_work(&dir);
+            update_task(
+                dir.clone(),
+                TaskState {
+                    state: "failure".to_string(),
+                    percent: 10,
+                    message: err,
+                },
+            );
+            continue;
         }
         update_task(
             dir.clone(),
@@ -520,7 +987,55 @@ async fn download_work_from_task_list() -> Result<String, String> {
                 message: "".to_string(),
             },
         );
-        match download_work_to(&work.unwrap(), dir.clone()).await {
+        let selection = read_selection(dir.clone());
+        let work = work.unwrap().select(&selection);
+        let mut result = download_work_to(&work, dir.clone(), &selection).await;
+        if let (Ok(_), Some(profile)) = (&result, selection.transcode.clone()) {
+            let task_dir = dir.clone();
+            result = match tokio::task::spawn_blocking(move || {
+                transcode_files(&task_dir, &work, &profile, None)
+            })
+            .await
+            {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(err)) => Err(err),
+                Err(err) => Err(err.to_string()),
+            };
+        }
+        if result.is_ok() {
+            // a re-download brings back the faces as hosted, hide them again,
+            // after transcoding so the faces carry the names `read_local_work` expects
+            let task_dir = dir.clone();
+            result = match tokio::task::spawn_blocking(move || {
+                forget_originals(&task_dir, None)?;
+                apply_redactions(&task_dir, &read_redactions(&task_dir))
+            })
+            .await
+            {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(err)) => Err(err),
+                Err(err) => Err(err.to_string()),
+            };
+        }
+        if result.is_ok() {
+            // thumbnails are a convenience for the library, never fail the download
+            let task_dir = dir.clone();
+            if let Ok(Err(err)) = tokio::task::spawn_blocking(move || {
+                generate_thumbnails(&task_dir, MODE_FRONT, DEFAULT_THUMBNAIL_SIZE)
+            })
+            .await
+            {
+                println!("generate_thumbnails error {}", err);
+            }
+        }
+        let (task_app, task_dir, ok) = (app.clone(), dir.clone(), result.is_ok());
+        match tokio::task::spawn_blocking(move || finish_download(&task_app, &task_dir, ok)).await {
+            Ok(Err(err)) => println!("finish_download error {}", err),
+            Err(err) => println!("finish_download error {}", err),
+            Ok(Ok(_)) => {}
+        }
+        unlock_work(&dir);
+        match result {
             Ok(_) => update_task(
                 dir.clone(),
                 TaskState {
@@ -547,3 +1062,176 @@ async fn download_work_from_task_list() -> Result<String, String> {
 pub async fn query_all_task_state() -> HashMap<String, TaskState> {
     get_task_state()
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{
+        read_local_work, with_jsonp_suffix, write_selection, Initial, Model, Observer, Panorama,
+        PanoramaItem, Quaternion, Selection, Work,
+    };
+    use crate::util::transcode::TranscodeProfile;
+    use std::fs;
+
+    // four panoramas in a row, 0 and 1 on floor 0, 2 and 3 on floor 1
+    pub(crate) fn sample() -> Work {
+        let observers: Vec<Observer> = (0..4)
+            .map(|index| Observer {
+                accessible_nodes: vec![index - 1, index + 1].into_iter().filter(|i| (0..4).contains(i)).collect(),
+                floor_index: index / 2,
+                index: index,
+                offset_point_count: 0,
+                position: vec![index as f64, 0.0, 0.0],
+                quaternion: Quaternion {
+                    w: 1.0,
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                standing_position: vec![index as f64, -1.0, 0.0],
+                visible_nodes: (0..4).filter(|i| *i != index).collect(),
+            })
+            .collect();
+        Work {
+            base_url: String::from("https://example.com/work/"),
+            initial: Initial {
+                flag_position: None,
+                fov: 90,
+                heading: Some(30),
+                latitude: 0.0,
+                longitude: 0.0,
+                pano: Some(2),
+                pano_index: Some(2),
+            },
+            model: Model {
+                file_url: String::from("model/m.at3d"),
+                material_base_url: String::from("model/"),
+                material_textures: vec![String::from("t0.jpg"), String::from("t1.jpg")],
+                model_type: 1,
+            },
+            observers: observers,
+            panorama: Panorama {
+                count: 4,
+                list: (0..4).map(|index| PanoramaItem::from_face_dir(index, &format!("images/{}", index))).collect(),
+            },
+            picture_url: String::from("https://example.com/p.jpg"),
+            title_picture_url: String::from("https://example.com/t.jpg"),
+        }
+    }
+
+    #[test]
+    fn select_panoramas_and_floors() {
+        let selection = Selection {
+            panoramas: Some(vec![1, 2, 3]),
+            floors: Some(vec![1]),
+            ..Selection::default()
+        };
+        let work = sample().select(&selection);
+        let indexes: Vec<i64> = work.observers.iter().map(|o| o.index).collect();
+        assert_eq!(indexes, vec![0, 1]);
+        // 2 and 3 renumbered, the links to 1 dropped
+        assert_eq!(work.observers[0].position, vec![2.0, 0.0, 0.0]);
+        assert_eq!(work.observers[0].accessible_nodes, vec![1]);
+        assert_eq!(work.observers[1].visible_nodes, vec![0]);
+        assert_eq!(work.panorama.count, 2);
+        assert_eq!(work.panorama.list[0].index, 0);
+        assert_eq!(work.panorama.list[0].front, "images/2/front.jpg");
+        assert_eq!(work.initial.pano_index, Some(0));
+        assert_eq!(work.initial.heading, Some(30));
+
+        // the initial panorama is gone: start on the first one, no heading
+        let work = sample().select(&Selection {
+            panoramas: Some(vec![0, 3]),
+            ..Selection::default()
+        });
+        assert_eq!(work.panorama.list[1].front, "images/3/front.jpg");
+        assert_eq!(work.initial.pano_index, Some(0));
+        assert_eq!(work.initial.pano, Some(0));
+        assert_eq!(work.initial.heading, None);
+
+        // no list keeps everything as is
+        let work = sample().select(&Selection::default());
+        assert_eq!(work.observers.len(), 4);
+        assert_eq!(work.initial.pano_index, Some(2));
+    }
+
+    #[test]
+    fn select_asset_classes() {
+        let all = sample().get_download_list().len();
+        assert_eq!(all, 2 + 4 * 6 + 1 + 2);
+
+        let work = sample().select(&Selection {
+            textures: false,
+            ..Selection::default()
+        });
+        assert_eq!(work.model.file_url, "model/m.at3d");
+        assert!(work.model.material_textures.is_empty());
+        assert_eq!(work.get_download_list().len(), all - 2);
+
+        // no model, no textures either
+        let work = sample().select(&Selection {
+            model: false,
+            ..Selection::default()
+        });
+        assert!(work.model.file_url.is_empty());
+        assert!(work.model.material_textures.is_empty());
+        assert_eq!(work.get_download_list().len(), all - 3);
+
+        let work = sample().select(&Selection {
+            pictures: false,
+            ..Selection::default()
+        });
+        assert!(work.picture_url.is_empty());
+        assert!(work.title_picture_url.is_empty());
+        let download = work.get_download_list();
+        assert_eq!(download.len(), all - 2);
+        assert_eq!(download[0].1, "images/0/right.jpg");
+    }
+
+    #[test]
+    fn trimmed_work_js() {
+        let work = sample().select(&Selection {
+            panoramas: Some(vec![3]),
+            model: false,
+            pictures: false,
+            ..Selection::default()
+        });
+        let jsonp: Work = serde_json::from_str(&work.get_jsonp_work()).unwrap();
+        // numbered like the download list, blank assets skipped
+        let download = work.get_download_list();
+        assert_eq!(download.len(), 6);
+        assert!(jsonp.picture_url.is_empty());
+        assert!(jsonp.model.file_url.is_empty());
+        assert!(jsonp.model.material_textures.is_empty());
+        assert_eq!(jsonp.panorama.list.len(), 1);
+        assert_eq!(jsonp.panorama.list[0].right, with_jsonp_suffix(&download[0].1, 0));
+        assert_eq!(jsonp.panorama.list[0].down, with_jsonp_suffix(&download[5].1, 5));
+        assert_eq!(jsonp.panorama.list[0].right, "images/3/right.jpg.0.jsonp");
+        assert_eq!(jsonp.observers.len(), 1);
+        assert_eq!(jsonp.observers[0].index, 0);
+    }
+
+    #[test]
+    fn read_transcoded_renumbered_work() {
+        let dir = std::env::temp_dir().join("work_read_transcoded_renumbered_work");
+        fs::create_dir_all(&dir).unwrap();
+        let dir_str = dir.to_str().unwrap();
+        fs::write(dir.join("input.json"), serde_json::to_string(&sample()).unwrap()).unwrap();
+        let profile: TranscodeProfile = serde_json::from_str("{\"format\":\"webp\"}").unwrap();
+        let selection = Selection {
+            panoramas: Some(vec![1, 2]),
+            transcode: Some(profile),
+            ..Selection::default()
+        };
+        write_selection(dir_str, &selection).unwrap();
+
+        let work = read_local_work(dir_str).unwrap();
+        assert_eq!(work.panorama.list.len(), 2);
+        assert_eq!(work.panorama.list[1].index, 1);
+        assert_eq!(work.panorama.list[1].front, "images/2/front.webp");
+        assert_eq!(work.observers[1].position, vec![2.0, 0.0, 0.0]);
+        assert_eq!(work.initial.pano_index, Some(1));
+        // the model refers to its textures by name
+        assert_eq!(work.model.material_textures[0], "t0.jpg");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -14,10 +14,39 @@ use command::file::{
 use command::work::{
     add_work_download_task, query_all_task_state
 };
+use command::panorama::convert_equirect_to_cube;
+use command::builder::build_local_work;
+use command::model::export_model_glb;
+use command::pose::export_observer_poses;
+use command::graph::{analyze_work_graph, export_work_graph};
+use command::tour::generate_walkthrough;
+use command::split::split_work_by_floor;
+use command::merge::merge_works;
+use command::rehost::export_work_for_hosting;
+use command::publish::{list_publish_profiles, save_publish_profile, delete_publish_profile};
+use command::sftp::publish_work_sftp;
+use command::s3::publish_work_s3;
+use command::server::{start_preview_server, open_preview_server, stop_preview_server, list_preview_servers};
+use command::layout::{convert_work_layout, rebuild_work_preview};
+use command::single::{estimate_single_html, export_single_html};
+use command::transcode::transcode_work_images;
+use command::thumbnail::generate_work_thumbnails;
+use command::branding::set_work_branding;
+use command::redact::{add_work_redactions, list_work_redactions, reapply_work_redactions};
+use command::viewer::{init_templates, list_viewer_templates, set_work_viewer};
+use command::library::{
+    list_library_works,
+    rename_library_work,
+    relocate_library_work,
+    remove_library_work,
+    rescan_library_works
+};
+use command::sync::{get_work_source, check_work_updates, sync_work};
+use command::diff::diff_works;
 
 
 use command::http::{parse_js_code, parse_html_title};
-use tauri::{CustomMenuItem, Menu, MenuItem, Submenu};
+use tauri::{CustomMenuItem, Manager, Menu, MenuItem, Submenu};
 use tauri::{Window, WindowMenuEvent};
 
 fn main() {
@@ -36,6 +65,10 @@ fn main() {
     //let menu = Menu::os_default(&"sss");
     let ctx = tauri::generate_context!();
     tauri::Builder::default()
+        .setup(|app| {
+            init_templates(&app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             get_file_content,
             write_file,
@@ -52,6 +85,46 @@ fn main() {
             query_all_task_state,
             parse_js_code,
             parse_html_title,
+            convert_equirect_to_cube,
+            build_local_work,
+            export_model_glb,
+            export_observer_poses,
+            analyze_work_graph,
+            export_work_graph,
+            generate_walkthrough,
+            split_work_by_floor,
+            merge_works,
+            export_work_for_hosting,
+            list_publish_profiles,
+            save_publish_profile,
+            delete_publish_profile,
+            publish_work_sftp,
+            publish_work_s3,
+            start_preview_server,
+            open_preview_server,
+            stop_preview_server,
+            list_preview_servers,
+            convert_work_layout,
+            estimate_single_html,
+            export_single_html,
+            transcode_work_images,
+            generate_work_thumbnails,
+            set_work_branding,
+            add_work_redactions,
+            list_work_redactions,
+            reapply_work_redactions,
+            list_viewer_templates,
+            set_work_viewer,
+            rebuild_work_preview,
+            list_library_works,
+            rename_library_work,
+            relocate_library_work,
+            remove_library_work,
+            rescan_library_works,
+            get_work_source,
+            check_work_updates,
+            sync_work,
+            diff_works,
         ])
         .menu(menu)
         .on_menu_event(window_menu_event)
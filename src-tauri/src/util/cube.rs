@@ -0,0 +1,213 @@
+use image::{ImageBuffer, Rgb, RgbImage};
+use std::f64::consts::PI;
+use std::path::Path;
+
+// same order as the faces are listed in `PanoramaItem`
+pub const CUBE_FACES: [&str; 6] = ["right", "left", "front", "back", "up", "down"];
+
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+// direction of the ray through the face pixel (u, v), both in [-1, 1],
+// u grows to the right and v grows downwards on the face image.
+pub fn face_direction(face: &str, u: f64, v: f64) -> (f64, f64, f64) {
+    match face {
+        "front" => (u, -v, 1.0),
+        "back" => (-u, -v, -1.0),
+        "right" => (1.0, -v, -u),
+        "left" => (-1.0, -v, u),
+        "up" => (u, 1.0, v),
+        _ => (u, -1.0, -v),
+    }
+}
+
+// image right, image down and viewing direction of the camera that sees `face`,
+// in the right-handed frame of the observers (+Y up, front along +Z). cube maps
+// are sampled in a left-handed frame, `face_direction` is that frame with X mirrored
+pub fn face_axes(face: &str) -> [[f64; 3]; 3] {
+    let observer = |u: f64, v: f64| {
+        let (x, y, z) = face_direction(face, u, v);
+        [-x, y, z]
+    };
+    let forward = observer(0.0, 0.0);
+    let along = |point: [f64; 3]| [point[0] - forward[0], point[1] - forward[1], point[2] - forward[2]];
+    [along(observer(1.0, 0.0)), along(observer(0.0, 1.0)), forward]
+}
+
+fn sample_bilinear(source: &RgbImage, x: f64, y: f64) -> Rgb<u8> {
+    let width = source.width() as i64;
+    let height = source.height() as i64;
+    let x0 = x.floor() as i64;
+    let y0 = y.floor() as i64;
+    let dx = x - x0 as f64;
+    let dy = y - y0 as f64;
+    let pixel = |px: i64, py: i64| {
+        // longitude wraps around, latitude is clamped at the poles
+        let px = px.rem_euclid(width) as u32;
+        let py = py.clamp(0, height - 1) as u32;
+        source.get_pixel(px, py).0
+    };
+    let p00 = pixel(x0, y0);
+    let p10 = pixel(x0 + 1, y0);
+    let p01 = pixel(x0, y0 + 1);
+    let p11 = pixel(x0 + 1, y0 + 1);
+    let mut out = [0u8; 3];
+    for c in 0..3 {
+        let top = p00[c] as f64 * (1.0 - dx) + p10[c] as f64 * dx;
+        let bottom = p01[c] as f64 * (1.0 - dx) + p11[c] as f64 * dx;
+        out[c] = (top * (1.0 - dy) + bottom * dy).round().clamp(0.0, 255.0) as u8;
+    }
+    Rgb(out)
+}
+
+pub fn render_cube_face(source: &RgbImage, face: &str, size: u32) -> RgbImage {
+    let width = source.width() as f64;
+    let height = source.height() as f64;
+    ImageBuffer::from_fn(size, size, |i, j| {
+        let u = 2.0 * (i as f64 + 0.5) / size as f64 - 1.0;
+        let v = 2.0 * (j as f64 + 0.5) / size as f64 - 1.0;
+        let (x, y, z) = face_direction(face, u, v);
+        let longitude = x.atan2(z);
+        let latitude = y.atan2((x * x + z * z).sqrt());
+        let px = (longitude / (2.0 * PI) + 0.5) * width - 0.5;
+        let py = (0.5 - latitude / PI) * height - 0.5;
+        sample_bilinear(source, px, py)
+    })
+}
+
+// face and (u, v) in [-1, 1] hit by a direction, inverse of `face_direction`
+fn direction_face(x: f64, y: f64, z: f64) -> (usize, f64, f64) {
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    if ax >= ay && ax >= az {
+        if x > 0.0 {
+            (0, -z / x, -y / x)
+        } else {
+            (1, -z / x, y / x)
+        }
+    } else if az >= ay {
+        if z > 0.0 {
+            (2, x / z, -y / z)
+        } else {
+            (3, x / z, y / z)
+        }
+    } else if y > 0.0 {
+        (4, x / y, z / y)
+    } else {
+        (5, -x / y, z / y)
+    }
+}
+
+fn sample_clamped(source: &RgbImage, x: f64, y: f64) -> Rgb<u8> {
+    let px = x.round().clamp(0.0, source.width() as f64 - 1.0) as u32;
+    let py = y.round().clamp(0.0, source.height() as f64 - 1.0) as u32;
+    *source.get_pixel(px, py)
+}
+
+// equirectangular image of `width` x `width / 2` from faces in `CUBE_FACES` order
+pub fn render_equirect(faces: &Vec<RgbImage>, width: u32) -> RgbImage {
+    let height = (width / 2).max(1);
+    ImageBuffer::from_fn(width, height, |i, j| {
+        let longitude = ((i as f64 + 0.5) / width as f64 - 0.5) * 2.0 * PI;
+        let latitude = (0.5 - (j as f64 + 0.5) / height as f64) * PI;
+        let x = latitude.cos() * longitude.sin();
+        let y = latitude.sin();
+        let z = latitude.cos() * longitude.cos();
+        let (face, u, v) = direction_face(x, y, z);
+        let source = &faces[face];
+        let px = (u + 1.0) / 2.0 * source.width() as f64 - 0.5;
+        let py = (v + 1.0) / 2.0 * source.height() as f64 - 0.5;
+        sample_clamped(source, px, py)
+    })
+}
+
+pub fn encode_jpeg(img: &RgbImage, quality: u8) -> Result<Vec<u8>, String> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality);
+    if let Err(err) = encoder.encode_image(img) {
+        return Err(format!("encode jpeg failed: {}", err));
+    }
+    Ok(buffer)
+}
+
+pub fn write_jpeg(img: &RgbImage, dest: &str, quality: u8) -> Result<(), String> {
+    let path = Path::new(dest);
+    if let Some(parent) = path.parent() {
+        if let Err(err) = std::fs::create_dir_all(parent) {
+            return Err(err.to_string());
+        }
+    }
+    let file = std::fs::File::create(path);
+    if let Err(err) = file {
+        return Err(format!("create `{}` failed: {}", dest, err));
+    }
+    let mut writer = std::io::BufWriter::new(file.unwrap());
+    let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut writer, quality);
+    if let Err(err) = encoder.encode_image(img) {
+        return Err(format!("encode `{}` failed: {}", dest, err));
+    }
+    Ok(())
+}
+
+/// Cut an equirectangular panorama into the six cube faces and write them
+/// as `<dir>/<face>.jpg`. A `size` of 0 picks a quarter of the source width.
+pub fn equirect_to_cube_files(src: &str, dir: &str, size: u32) -> Result<(), String> {
+    let source = image::open(src);
+    if let Err(err) = source {
+        return Err(format!("open `{}` failed: {}", src, err));
+    }
+    let source = source.unwrap().to_rgb8();
+    let size = if size == 0 { source.width() / 4 } else { size };
+    if size == 0 {
+        return Err(format!("`{}` is too small to convert", src));
+    }
+    for face in CUBE_FACES.iter() {
+        let img = render_cube_face(&source, face, size);
+        let dest = Path::new(dir).join(format!("{}.jpg", face));
+        write_jpeg(&img, dest.to_str().unwrap(), DEFAULT_JPEG_QUALITY)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{direction_face, face_axes, face_direction, render_cube_face, render_equirect, CUBE_FACES};
+    use image::{ImageBuffer, Rgb, RgbImage};
+
+    #[test]
+    fn direction_face_inverts_face_direction() {
+        for (index, face) in CUBE_FACES.iter().enumerate() {
+            for (u, v) in [(0.0, 0.0), (0.5, -0.25), (-0.75, 0.9), (0.3, 0.6)] {
+                let (x, y, z) = face_direction(face, u, v);
+                let (hit, hu, hv) = direction_face(x, y, z);
+                assert_eq!(hit, index, "{} ({}, {})", face, u, v);
+                assert!((hu - u).abs() < 1e-9 && (hv - v).abs() < 1e-9, "{} ({}, {})", face, u, v);
+            }
+        }
+    }
+
+    #[test]
+    fn face_axes_are_right_handed() {
+        for face in CUBE_FACES.iter() {
+            let [r, d, f] = face_axes(face);
+            // right x down is the viewing direction for a camera with +Y down
+            let cross = [r[1] * d[2] - r[2] * d[1], r[2] * d[0] - r[0] * d[2], r[0] * d[1] - r[1] * d[0]];
+            for i in 0..3 {
+                assert!((cross[i] - f[i]).abs() < 1e-9, "{}", face);
+            }
+        }
+    }
+
+    #[test]
+    fn cube_faces_round_trip() {
+        // every face of the cube in its own colour
+        let colours = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 0], [0, 255, 255], [255, 0, 255]];
+        let faces: Vec<RgbImage> = colours
+            .iter()
+            .map(|c| ImageBuffer::from_pixel(16, 16, Rgb(*c)))
+            .collect();
+        let equirect = render_equirect(&faces, 256);
+        for (index, face) in CUBE_FACES.iter().enumerate() {
+            let back = render_cube_face(&equirect, face, 8);
+            assert_eq!(*back.get_pixel(4, 4), Rgb(colours[index]), "{}", face);
+        }
+    }
+}
@@ -78,4 +78,42 @@ pub async fn download_text(url: &str) -> Result<String, String> {
         Ok(res) => Ok(res.text().await.unwrap()),
         Err(err) => Err(err.to_string()),
     }
-}
\ No newline at end of file
+}
+
+// every file under `dir` as (relative path with `/` separators, size)
+pub fn list_files(dir: &str) -> Result<Vec<(String, u64)>, String> {
+    let mut list: Vec<(String, u64)> = Vec::new();
+    let mut dirs: Vec<std::path::PathBuf> = vec![Path::new(dir).to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        let entry = fs::read_dir(&current);
+        if let Err(err) = entry {
+            return Err(format!("read dir `{}` error: {}", current.display(), err));
+        }
+        for item in entry.unwrap().flatten() {
+            let path = item.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let size = item.metadata().map(|m| m.len()).unwrap_or(0);
+            let relative = path.strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/");
+            list.push((relative, size));
+        }
+    }
+    list.sort();
+    Ok(list)
+}
+
+// copy every file under `src` into `dest`, keeping the relative paths
+pub fn copy_dir(src: &str, dest: &str) -> Result<(), String> {
+    for (name, _) in list_files(src)? {
+        let target = Path::new(dest).join(&name);
+        if let Err(err) = fs::create_dir_all(target.parent().unwrap()) {
+            return Err(err.to_string());
+        }
+        if let Err(err) = fs::copy(Path::new(src).join(&name), &target) {
+            return Err(format!("copy `{}` error: {}", name, err));
+        }
+    }
+    Ok(())
+}
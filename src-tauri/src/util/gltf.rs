@@ -0,0 +1,287 @@
+use serde_json::{json, Value};
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+pub struct GltfPrimitive {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+    pub material: Option<usize>,
+}
+
+pub struct GltfMaterial {
+    pub name: String,
+    pub base_color: [f32; 4],
+    pub texture: Option<usize>,
+}
+
+pub struct GltfImage {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+pub struct GltfNode {
+    pub name: String,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+    pub translation: Option<[f64; 3]>,
+    pub rotation: Option<[f64; 4]>,
+}
+
+pub struct GltfCamera {
+    pub name: String,
+    pub yfov: f64,
+    pub aspect_ratio: Option<f64>,
+}
+
+// a minimal glTF 2.0 scene that is written into a single binary GLB
+#[derive(Default)]
+pub struct GltfDocument {
+    pub meshes: Vec<(String, Vec<GltfPrimitive>)>,
+    pub materials: Vec<GltfMaterial>,
+    pub images: Vec<GltfImage>,
+    pub cameras: Vec<GltfCamera>,
+    pub nodes: Vec<GltfNode>,
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GltfDocument {
+    pub fn new() -> GltfDocument {
+        GltfDocument::default()
+    }
+
+    fn push_buffer_view(&mut self, data: &[u8], target: Option<u32>) -> usize {
+        while self.buffer.len() % 4 != 0 {
+            self.buffer.push(0);
+        }
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": data.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.buffer.extend_from_slice(data);
+        self.buffer_views.push(view);
+        self.buffer_views.len() - 1
+    }
+
+    fn push_vec3_accessor(&mut self, values: &Vec<[f32; 3]>, with_bounds: bool) -> usize {
+        let mut data: Vec<u8> = Vec::with_capacity(values.len() * 12);
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for value in values.iter() {
+            for i in 0..3 {
+                data.extend_from_slice(&value[i].to_le_bytes());
+                min[i] = min[i].min(value[i]);
+                max[i] = max[i].max(value[i]);
+            }
+        }
+        let view = self.push_buffer_view(&data, Some(ARRAY_BUFFER));
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": values.len(),
+            "type": "VEC3",
+        });
+        if with_bounds {
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_vec2_accessor(&mut self, values: &Vec<[f32; 2]>) -> usize {
+        let mut data: Vec<u8> = Vec::with_capacity(values.len() * 8);
+        for value in values.iter() {
+            data.extend_from_slice(&value[0].to_le_bytes());
+            data.extend_from_slice(&value[1].to_le_bytes());
+        }
+        let view = self.push_buffer_view(&data, Some(ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": values.len(),
+            "type": "VEC2",
+        }));
+        self.accessors.len() - 1
+    }
+
+    fn push_index_accessor(&mut self, values: &Vec<u32>) -> usize {
+        let mut data: Vec<u8> = Vec::with_capacity(values.len() * 4);
+        for value in values.iter() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let view = self.push_buffer_view(&data, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": values.len(),
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+
+    fn build_json(&mut self) -> Value {
+        let meshes = std::mem::take(&mut self.meshes);
+        let mut mesh_list: Vec<Value> = Vec::new();
+        for (name, primitives) in meshes.iter() {
+            let mut primitive_list: Vec<Value> = Vec::new();
+            for primitive in primitives.iter() {
+                let mut attributes = json!({
+                    "POSITION": self.push_vec3_accessor(&primitive.positions, true),
+                });
+                if !primitive.normals.is_empty() {
+                    attributes["NORMAL"] = json!(self.push_vec3_accessor(&primitive.normals, false));
+                }
+                if !primitive.uvs.is_empty() {
+                    attributes["TEXCOORD_0"] = json!(self.push_vec2_accessor(&primitive.uvs));
+                }
+                let mut item = json!({
+                    "attributes": attributes,
+                    "indices": self.push_index_accessor(&primitive.indices),
+                    "mode": 4,
+                });
+                if let Some(material) = primitive.material {
+                    item["material"] = json!(material);
+                }
+                primitive_list.push(item);
+            }
+            mesh_list.push(json!({"name": name, "primitives": primitive_list}));
+        }
+        self.meshes = meshes;
+
+        let images = std::mem::take(&mut self.images);
+        let mut image_list: Vec<Value> = Vec::new();
+        for image in images.iter() {
+            let view = self.push_buffer_view(&image.data, None);
+            image_list.push(json!({"bufferView": view, "mimeType": image.mime_type}));
+        }
+        self.images = images;
+
+        let material_list: Vec<Value> = self
+            .materials
+            .iter()
+            .map(|material| {
+                let mut pbr = json!({
+                    "baseColorFactor": material.base_color,
+                    "metallicFactor": 0.0,
+                    "roughnessFactor": 1.0,
+                });
+                if let Some(texture) = material.texture {
+                    pbr["baseColorTexture"] = json!({ "index": texture });
+                }
+                let mut item = json!({
+                    "name": material.name,
+                    "pbrMetallicRoughness": pbr,
+                    "doubleSided": true,
+                });
+                if material.base_color[3] < 1.0 {
+                    item["alphaMode"] = json!("BLEND");
+                }
+                item
+            })
+            .collect();
+        let texture_list: Vec<Value> = (0..image_list.len())
+            .map(|index| json!({"sampler": 0, "source": index}))
+            .collect();
+        let camera_list: Vec<Value> = self
+            .cameras
+            .iter()
+            .map(|camera| {
+                let mut perspective = json!({"yfov": camera.yfov, "znear": 0.01});
+                if let Some(aspect_ratio) = camera.aspect_ratio {
+                    perspective["aspectRatio"] = json!(aspect_ratio);
+                }
+                json!({"name": camera.name, "type": "perspective", "perspective": perspective})
+            })
+            .collect();
+        let node_list: Vec<Value> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut item = json!({ "name": node.name });
+                if let Some(mesh) = node.mesh {
+                    item["mesh"] = json!(mesh);
+                }
+                if let Some(camera) = node.camera {
+                    item["camera"] = json!(camera);
+                }
+                if let Some(translation) = node.translation {
+                    item["translation"] = json!(translation);
+                }
+                if let Some(rotation) = node.rotation {
+                    item["rotation"] = json!(rotation);
+                }
+                item
+            })
+            .collect();
+
+        while self.buffer.len() % 4 != 0 {
+            self.buffer.push(0);
+        }
+        let mut root = json!({
+            "asset": {"version": "2.0", "generator": "tauri-vrd"},
+            "scene": 0,
+            "scenes": [{"nodes": (0..node_list.len()).collect::<Vec<usize>>()}],
+            "nodes": node_list,
+        });
+        if !mesh_list.is_empty() {
+            root["meshes"] = json!(mesh_list);
+        }
+        if !material_list.is_empty() {
+            root["materials"] = json!(material_list);
+        }
+        if !texture_list.is_empty() {
+            root["textures"] = json!(texture_list);
+            root["images"] = json!(image_list);
+            root["samplers"] = json!([{"magFilter": 9729, "minFilter": 9987, "wrapS": 10497, "wrapT": 10497}]);
+        }
+        if !camera_list.is_empty() {
+            root["cameras"] = json!(camera_list);
+        }
+        if !self.buffer.is_empty() {
+            root["buffers"] = json!([{ "byteLength": self.buffer.len() }]);
+            root["bufferViews"] = json!(self.buffer_views);
+            root["accessors"] = json!(self.accessors);
+        }
+        root
+    }
+
+    pub fn to_glb(mut self) -> Vec<u8> {
+        let mut json_chunk = serde_json::to_vec(&self.build_json()).unwrap();
+        while json_chunk.len() % 4 != 0 {
+            json_chunk.push(b' ');
+        }
+        let mut total = 12 + 8 + json_chunk.len();
+        if !self.buffer.is_empty() {
+            total = total + 8 + self.buffer.len();
+        }
+        let mut glb: Vec<u8> = Vec::with_capacity(total);
+        glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+        glb.extend_from_slice(&GLB_VERSION.to_le_bytes());
+        glb.extend_from_slice(&(total as u32).to_le_bytes());
+        glb.extend_from_slice(&(json_chunk.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&CHUNK_JSON.to_le_bytes());
+        glb.extend_from_slice(&json_chunk);
+        if !self.buffer.is_empty() {
+            glb.extend_from_slice(&(self.buffer.len() as u32).to_le_bytes());
+            glb.extend_from_slice(&CHUNK_BIN.to_le_bytes());
+            glb.extend_from_slice(&self.buffer);
+        }
+        glb
+    }
+}
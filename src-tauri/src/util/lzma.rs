@@ -0,0 +1,310 @@
+// decoder of the `.lzma` (LZMA alone) streams five.js unpacks with LZMA-JS,
+// a port of the reference decoder of the LZMA SDK (LzmaSpec.cpp)
+const NUM_STATES: usize = 12;
+const NUM_POS_BITS_MAX: usize = 4;
+const NUM_LEN_TO_POS_STATES: usize = 4;
+const NUM_ALIGN_BITS: u32 = 4;
+const START_POS_MODEL_INDEX: u32 = 4;
+const END_POS_MODEL_INDEX: u32 = 14;
+const NUM_FULL_DISTANCES: usize = 1 << (END_POS_MODEL_INDEX >> 1);
+const MATCH_MIN_LEN: usize = 2;
+const BIT_MODEL_TOTAL: u32 = 1 << 11;
+const NUM_MOVE_BITS: u32 = 5;
+const PROB_INIT: u16 = (BIT_MODEL_TOTAL / 2) as u16;
+const TOP_VALUE: u32 = 1 << 24;
+const HEADER_SIZE: usize = 13;
+
+struct RangeDecoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+    range: u32,
+    code: u32,
+}
+
+impl<'a> RangeDecoder<'a> {
+    fn new(data: &'a [u8]) -> Result<RangeDecoder<'a>, String> {
+        if data.len() < 5 || data[0] != 0 {
+            return Err(String::from("lzma stream is corrupted"));
+        }
+        let code = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+        if code == u32::MAX {
+            return Err(String::from("lzma stream is corrupted"));
+        }
+        Ok(RangeDecoder {
+            data,
+            pos: 5,
+            range: u32::MAX,
+            code,
+        })
+    }
+
+    fn normalize(&mut self) -> Result<(), String> {
+        if self.range < TOP_VALUE {
+            let byte = match self.data.get(self.pos) {
+                Some(byte) => *byte,
+                None => return Err(String::from("lzma stream is truncated")),
+            };
+            self.pos = self.pos + 1;
+            self.range = self.range << 8;
+            self.code = (self.code << 8) | byte as u32;
+        }
+        Ok(())
+    }
+
+    fn direct_bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut result: u32 = 0;
+        for _ in 0..count {
+            self.range = self.range >> 1;
+            self.code = self.code.wrapping_sub(self.range);
+            let t = 0u32.wrapping_sub(self.code >> 31);
+            self.code = self.code.wrapping_add(self.range & t);
+            if self.code == self.range {
+                return Err(String::from("lzma stream is corrupted"));
+            }
+            self.normalize()?;
+            result = (result << 1).wrapping_add(t.wrapping_add(1));
+        }
+        Ok(result)
+    }
+
+    fn bit(&mut self, prob: &mut u16) -> Result<u32, String> {
+        let value = *prob as u32;
+        let bound = (self.range >> 11) * value;
+        let symbol = if self.code < bound {
+            *prob = (value + ((BIT_MODEL_TOTAL - value) >> NUM_MOVE_BITS)) as u16;
+            self.range = bound;
+            0
+        } else {
+            *prob = (value - (value >> NUM_MOVE_BITS)) as u16;
+            self.code = self.code - bound;
+            self.range = self.range - bound;
+            1
+        };
+        self.normalize()?;
+        Ok(symbol)
+    }
+
+    fn bit_tree(&mut self, probs: &mut [u16], count: u32) -> Result<u32, String> {
+        let mut m: usize = 1;
+        for _ in 0..count {
+            m = (m << 1) + self.bit(&mut probs[m])? as usize;
+        }
+        Ok(m as u32 - (1 << count))
+    }
+
+    fn bit_tree_reverse(&mut self, probs: &mut [u16], count: u32) -> Result<u32, String> {
+        let mut m: usize = 1;
+        let mut symbol: u32 = 0;
+        for i in 0..count {
+            let bit = self.bit(&mut probs[m])?;
+            m = (m << 1) + bit as usize;
+            symbol = symbol | (bit << i);
+        }
+        Ok(symbol)
+    }
+}
+
+struct LenDecoder {
+    choice: u16,
+    choice2: u16,
+    low: [[u16; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+    mid: [[u16; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+    high: [u16; 1 << 8],
+}
+
+impl LenDecoder {
+    fn new() -> LenDecoder {
+        LenDecoder {
+            choice: PROB_INIT,
+            choice2: PROB_INIT,
+            low: [[PROB_INIT; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+            mid: [[PROB_INIT; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+            high: [PROB_INIT; 1 << 8],
+        }
+    }
+
+    fn decode(&mut self, rc: &mut RangeDecoder, pos_state: usize) -> Result<usize, String> {
+        if rc.bit(&mut self.choice)? == 0 {
+            return Ok(rc.bit_tree(&mut self.low[pos_state], 3)? as usize);
+        }
+        if rc.bit(&mut self.choice2)? == 0 {
+            return Ok(8 + rc.bit_tree(&mut self.mid[pos_state], 3)? as usize);
+        }
+        Ok(16 + rc.bit_tree(&mut self.high, 8)? as usize)
+    }
+}
+
+// state after a literal
+fn literal_state(state: usize) -> usize {
+    if state < 4 {
+        0
+    } else if state < 10 {
+        state - 3
+    } else {
+        state - 6
+    }
+}
+
+// the whole stream of `data`: 13 bytes of header then the compressed data
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < HEADER_SIZE {
+        return Err(String::from("lzma header is truncated"));
+    }
+    let mut props = data[0] as usize;
+    if props >= 9 * 5 * 5 {
+        return Err(String::from("lzma properties are invalid"));
+    }
+    let lc = props % 9;
+    props = props / 9;
+    let lp = props % 5;
+    let pb = props / 5;
+    let dict_size = u32::from_le_bytes([data[1], data[2], data[3], data[4]]).max(1 << 12) as usize;
+    let mut size_bytes = [0u8; 8];
+    size_bytes.copy_from_slice(&data[5..13]);
+    let unpack_size = match u64::from_le_bytes(size_bytes) {
+        u64::MAX => None,
+        size => Some(size as usize),
+    };
+
+    let mut rc = RangeDecoder::new(&data[HEADER_SIZE..])?;
+    let mut out: Vec<u8> = Vec::with_capacity(unpack_size.unwrap_or(0).min(1 << 28));
+    let mut literal_probs = vec![PROB_INIT; 0x300 << (lc + lp)];
+    let mut pos_slot = [[PROB_INIT; 1 << 6]; NUM_LEN_TO_POS_STATES];
+    let mut pos_decoders = [PROB_INIT; 1 + NUM_FULL_DISTANCES - END_POS_MODEL_INDEX as usize];
+    let mut align = [PROB_INIT; 1 << NUM_ALIGN_BITS];
+    let mut is_match = [PROB_INIT; NUM_STATES << NUM_POS_BITS_MAX];
+    let mut is_rep = [PROB_INIT; NUM_STATES];
+    let mut is_rep_g0 = [PROB_INIT; NUM_STATES];
+    let mut is_rep_g1 = [PROB_INIT; NUM_STATES];
+    let mut is_rep_g2 = [PROB_INIT; NUM_STATES];
+    let mut is_rep0_long = [PROB_INIT; NUM_STATES << NUM_POS_BITS_MAX];
+    let mut len_decoder = LenDecoder::new();
+    let mut rep_len_decoder = LenDecoder::new();
+    let (mut rep0, mut rep1, mut rep2, mut rep3): (usize, usize, usize, usize) = (0, 0, 0, 0);
+    let mut state: usize = 0;
+
+    loop {
+        if unpack_size == Some(out.len()) {
+            return Ok(out);
+        }
+        let pos_state = out.len() & ((1 << pb) - 1);
+        if rc.bit(&mut is_match[(state << NUM_POS_BITS_MAX) + pos_state])? == 0 {
+            let prev_byte = out.last().cloned().unwrap_or(0) as usize;
+            let lit_state = ((out.len() & ((1 << lp) - 1)) << lc) + (prev_byte >> (8 - lc));
+            let probs = &mut literal_probs[0x300 * lit_state..0x300 * (lit_state + 1)];
+            let mut symbol: usize = 1;
+            if state >= 7 {
+                let mut match_byte = out[out.len() - rep0 - 1] as usize;
+                while symbol < 0x100 {
+                    let match_bit = (match_byte >> 7) & 1;
+                    match_byte = match_byte << 1;
+                    let bit = rc.bit(&mut probs[((1 + match_bit) << 8) + symbol])? as usize;
+                    symbol = (symbol << 1) | bit;
+                    if match_bit != bit {
+                        break;
+                    }
+                }
+            }
+            while symbol < 0x100 {
+                symbol = (symbol << 1) | rc.bit(&mut probs[symbol])? as usize;
+            }
+            out.push((symbol - 0x100) as u8);
+            state = literal_state(state);
+            continue;
+        }
+
+        let len;
+        if rc.bit(&mut is_rep[state])? != 0 {
+            if out.is_empty() {
+                return Err(String::from("lzma stream is corrupted"));
+            }
+            if rc.bit(&mut is_rep_g0[state])? == 0 {
+                if rc.bit(&mut is_rep0_long[(state << NUM_POS_BITS_MAX) + pos_state])? == 0 {
+                    state = if state < 7 { 9 } else { 11 };
+                    out.push(out[out.len() - rep0 - 1]);
+                    continue;
+                }
+            } else {
+                let dist;
+                if rc.bit(&mut is_rep_g1[state])? == 0 {
+                    dist = rep1;
+                } else {
+                    if rc.bit(&mut is_rep_g2[state])? == 0 {
+                        dist = rep2;
+                    } else {
+                        dist = rep3;
+                        rep3 = rep2;
+                    }
+                    rep2 = rep1;
+                }
+                rep1 = rep0;
+                rep0 = dist;
+            }
+            len = rep_len_decoder.decode(&mut rc, pos_state)?;
+            state = if state < 7 { 8 } else { 11 };
+        } else {
+            rep3 = rep2;
+            rep2 = rep1;
+            rep1 = rep0;
+            len = len_decoder.decode(&mut rc, pos_state)?;
+            state = if state < 7 { 7 } else { 10 };
+
+            let slot = rc.bit_tree(&mut pos_slot[len.min(NUM_LEN_TO_POS_STATES - 1)], 6)?;
+            rep0 = if slot < START_POS_MODEL_INDEX {
+                slot as usize
+            } else {
+                let direct = (slot >> 1) - 1;
+                let base = ((2 | (slot & 1)) << direct) as usize;
+                if slot < END_POS_MODEL_INDEX {
+                    base + rc.bit_tree_reverse(&mut pos_decoders[base - slot as usize..], direct)? as usize
+                } else {
+                    let high = (rc.direct_bits(direct - NUM_ALIGN_BITS)? << NUM_ALIGN_BITS) as usize;
+                    (base + high + rc.bit_tree_reverse(&mut align, NUM_ALIGN_BITS)? as usize) & 0xFFFF_FFFF
+                }
+            };
+            // end marker
+            if rep0 == 0xFFFF_FFFF {
+                return match unpack_size {
+                    Some(size) if size != out.len() => Err(String::from("lzma stream ended early")),
+                    _ => Ok(out),
+                };
+            }
+            if rep0 >= dict_size || rep0 >= out.len() {
+                return Err(String::from("lzma stream is corrupted"));
+            }
+        }
+        let len = len + MATCH_MIN_LEN;
+        if let Some(size) = unpack_size {
+            if out.len() + len > size {
+                return Err(String::from("lzma stream is corrupted"));
+            }
+        }
+        for _ in 0..len {
+            out.push(out[out.len() - rep0 - 1]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decompress;
+
+    // `lzma.compress(b"hello hello hello lzma", format=lzma.FORMAT_ALONE)` of python,
+    // which writes an unknown size and an end marker
+    const HELLO: [u8; 35] = [
+        0x5d, 0x00, 0x00, 0x80, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x34, 0x19,
+        0x49, 0xee, 0x8d, 0xe9, 0x50, 0x95, 0xfc, 0x0b, 0xee, 0xfb, 0x41, 0xee, 0xa9, 0xff, 0xff, 0xee,
+        0x89, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn decompress_with_end_marker() {
+        assert_eq!(decompress(&HELLO).unwrap(), b"hello hello hello lzma".to_vec());
+    }
+
+    #[test]
+    fn decompress_truncated() {
+        assert!(decompress(&HELLO[..20]).is_err());
+        assert!(decompress(&HELLO[..8]).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+// content type of a work file by its extension, jsonp wrappers are scripts
+pub fn mime_type(file_name: &str) -> &'static str {
+    let lower = file_name.to_lowercase();
+    let ext = lower.rsplit('.').next().unwrap_or("");
+    match ext {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ktx" => "image/ktx",
+        "js" | "jsonp" => "application/javascript",
+        "json" => "application/json",
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css",
+        "txt" | "obj" | "mtl" => "text/plain",
+        "glb" => "model/gltf-binary",
+        "gltf" => "model/gltf+json",
+        "tar" => "application/x-tar",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+// `type/subtype` made of http token chars, anything else can not go into a data uri
+pub fn is_mime_token(text: &str) -> bool {
+    let token = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+    };
+    match text.split_once('/') {
+        Some((kind, subtype)) => token(kind) && token(subtype),
+        None => false,
+    }
+}
+
+const KTX1_MAGIC: &[u8] = b"\xABKTX 11\xBB\r\n\x1A\n";
+const KTX2_MAGIC: &[u8] = b"\xABKTX 20\xBB\r\n\x1A\n";
+// bytes looked at for the text formats
+const SNIFF_LEN: usize = 4096;
+
+// a gltf document is a json object with an `asset` member
+fn is_gltf_json(head: &str) -> bool {
+    head.trim_start().starts_with('{') && head.contains("\"asset\"")
+}
+
+// every line of an obj file starts with a known keyword, vertices are mandatory
+fn is_obj(head: &str, truncated: bool) -> bool {
+    let mut vertices = false;
+    let mut lines: Vec<&str> = head.lines().collect();
+    if truncated {
+        // the last line may be cut at SNIFF_LEN
+        lines.pop();
+    }
+    for line in lines {
+        let keyword = line.split_whitespace().next().unwrap_or("");
+        match keyword {
+            "" => {}
+            "v" => vertices = true,
+            "vt" | "vn" | "vp" | "f" | "l" | "o" | "g" | "s" | "usemtl" | "mtllib" => {}
+            _ if keyword.starts_with('#') => {}
+            _ => return false,
+        }
+    }
+    vertices
+}
+
+// content type from the leading bytes of a file, None when nothing matches
+pub fn sniff_mime_type(content: &[u8]) -> Option<&'static str> {
+    if content.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some("image/jpeg");
+    }
+    if content.starts_with(b"\x89PNG\r\n\x1A\n") {
+        return Some("image/png");
+    }
+    if content.len() >= 12 && &content[0..4] == b"RIFF" && &content[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    if content.starts_with(KTX1_MAGIC) {
+        return Some("image/ktx");
+    }
+    if content.starts_with(KTX2_MAGIC) {
+        return Some("image/ktx2");
+    }
+    if content.starts_with(b"glTF") {
+        return Some("model/gltf-binary");
+    }
+    let head = &content[..content.len().min(SNIFF_LEN)];
+    let head = match std::str::from_utf8(head) {
+        Ok(head) => head,
+        // a multi-byte char cut at SNIFF_LEN
+        Err(err) if err.error_len().is_none() => std::str::from_utf8(&head[..err.valid_up_to()]).unwrap(),
+        Err(_) => return None,
+    };
+    if is_gltf_json(head) {
+        return Some("model/gltf+json");
+    }
+    if is_obj(head, content.len() > SNIFF_LEN) {
+        return Some("text/plain");
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_mime_token, sniff_mime_type, KTX1_MAGIC, KTX2_MAGIC, SNIFF_LEN};
+
+    #[test]
+    fn sniff_images() {
+        assert_eq!(sniff_mime_type(&[0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10]), Some("image/jpeg"));
+        assert_eq!(sniff_mime_type(b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR"), Some("image/png"));
+        assert_eq!(sniff_mime_type(b"RIFF\x24\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_mime_type(b"RIFF\x24\0\0\0WAVEfmt "), None);
+    }
+
+    #[test]
+    fn sniff_ktx() {
+        let mut ktx1 = KTX1_MAGIC.to_vec();
+        ktx1.extend_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(sniff_mime_type(&ktx1), Some("image/ktx"));
+        let mut ktx2 = KTX2_MAGIC.to_vec();
+        ktx2.extend_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(sniff_mime_type(&ktx2), Some("image/ktx2"));
+    }
+
+    #[test]
+    fn sniff_gltf() {
+        assert_eq!(sniff_mime_type(b"glTF\x02\0\0\0\x10\0\0\0"), Some("model/gltf-binary"));
+        let json = b"  {\"asset\": {\"version\": \"2.0\"}, \"nodes\": []}";
+        assert_eq!(sniff_mime_type(json), Some("model/gltf+json"));
+        assert_eq!(sniff_mime_type(b"{\"observers\": []}"), None);
+    }
+
+    #[test]
+    fn sniff_obj() {
+        let obj = b"# exported\nmtllib model.mtl\no room\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nusemtl wall\nf 1/1 2/1 3/1\n";
+        assert_eq!(sniff_mime_type(obj), Some("text/plain"));
+        // faces without vertices and unknown keywords are not obj
+        assert_eq!(sniff_mime_type(b"f 1 2 3\n"), None);
+        assert_eq!(sniff_mime_type(b"v 0 0 0\n<html>\n"), None);
+    }
+
+    #[test]
+    fn sniff_obj_cut_at_sniff_len() {
+        let mut obj = String::from("v 0 0 0\n# ab\n");
+        while obj.len() < SNIFF_LEN * 2 {
+            obj.push_str("usemtl wall\n");
+        }
+        // the last line looked at is cut to `use`, not a keyword on its own
+        assert_eq!(&obj[SNIFF_LEN - 3..SNIFF_LEN + 1], "usem");
+        assert_eq!(sniff_mime_type(obj.as_bytes()), Some("text/plain"));
+        // a multi-byte char cut at SNIFF_LEN
+        let mut obj = String::from("v 0 0 0\n# ");
+        while obj.len() < SNIFF_LEN + 8 {
+            obj.push('\u{00e9}');
+        }
+        assert_eq!(sniff_mime_type(obj.as_bytes()), Some("text/plain"));
+    }
+
+    #[test]
+    fn mime_tokens() {
+        assert!(is_mime_token("image/jpeg"));
+        assert!(is_mime_token("model/gltf+json"));
+        assert!(is_mime_token("application/vnd.ms-excel"));
+        assert!(!is_mime_token("image/jpeg\");alert(1);//"));
+        assert!(!is_mime_token("image/jpeg)"));
+        assert!(!is_mime_token("image"));
+        assert!(!is_mime_token("image/"));
+        assert!(!is_mime_token("text/html; charset=utf-8"));
+    }
+}
@@ -1 +1,10 @@
-pub mod file;
\ No newline at end of file
+pub mod file;
+pub mod cube;
+pub mod gltf;
+pub mod mime;
+pub mod s3;
+pub mod static_server;
+pub mod font;
+pub mod transcode;
+pub mod url;
+pub mod lzma;
\ No newline at end of file
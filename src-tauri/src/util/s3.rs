@@ -0,0 +1,445 @@
+use hmac::{Hmac, Mac};
+use md5::{Digest as _, Md5};
+use reqwest;
+use sha2::Sha256;
+use std::io::Read;
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SERVICE: &str = "s3";
+const DEFAULT_REGION: &str = "us-east-1";
+
+// path-style client for S3 compatible storages (AWS, MinIO, OSS, COS...)
+#[derive(Clone, Debug)]
+pub struct S3Client {
+    endpoint: String,
+    bucket: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+    client: reqwest::Client,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).unwrap();
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn uri_encode(input: &str, encode_slash: bool) -> String {
+    let mut out = String::new();
+    for b in input.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(b as char),
+            b'/' if !encode_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+// `k=v` pairs uri encoded and sorted by key
+fn canonical_query(query: &[(String, String)]) -> String {
+    let mut sorted_query: Vec<(String, String)> = query
+        .iter()
+        .map(|(k, v)| (uri_encode(k, true), uri_encode(v, true)))
+        .collect();
+    sorted_query.sort();
+    sorted_query
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+// the canonical request and its signed header list, `headers` are (name, value)
+fn canonical_request(
+    method: &str,
+    canonical_uri: &str,
+    canonical_query: &str,
+    headers: &[(&str, &str)],
+    payload_hash: &str,
+) -> (String, String) {
+    let mut headers: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect();
+    headers.sort();
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let signed_headers = headers.iter().map(|h| h.0.as_str()).collect::<Vec<&str>>().join(";");
+    let request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method, canonical_uri, canonical_query, canonical_headers, signed_headers, payload_hash
+    );
+    (request, signed_headers)
+}
+
+fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let mut key = hmac(format!("AWS4{}", secret_access_key).as_bytes(), date);
+    key = hmac(&key, region);
+    key = hmac(&key, service);
+    hmac(&key, "aws4_request")
+}
+
+// AWS signature version 4 of a canonical request, `amz_date` like 20130524T000000Z
+fn signature(
+    secret_access_key: &str,
+    amz_date: &str,
+    region: &str,
+    service: &str,
+    canonical_request: &str,
+) -> String {
+    let date = &amz_date[..8];
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        sha256_hex(canonical_request.as_bytes())
+    );
+    hex::encode(hmac(&signing_key(secret_access_key, date, region, service), &string_to_sign))
+}
+
+pub fn md5_hex(data: &[u8]) -> String {
+    hex::encode(Md5::digest(data))
+}
+
+// the etag S3 reports for an object uploaded in parts of `part_size`,
+// read one part at a time
+pub fn multipart_etag(mut reader: impl Read, part_size: usize) -> std::io::Result<String> {
+    let mut digests: Vec<u8> = Vec::new();
+    let mut count = 0;
+    let mut part: Vec<u8> = Vec::with_capacity(part_size);
+    loop {
+        part.clear();
+        (&mut reader).take(part_size as u64).read_to_end(&mut part)?;
+        if part.is_empty() {
+            break;
+        }
+        digests.extend_from_slice(&Md5::digest(&part));
+        count = count + 1;
+    }
+    Ok(format!("{}-{}", md5_hex(&digests), count))
+}
+
+// part `index` of a file uploaded in parts of `part_size`
+async fn read_part(local: &Path, index: u64, part_size: usize) -> Result<Vec<u8>, String> {
+    let file = tokio::fs::File::open(local).await;
+    if let Err(err) = file {
+        return Err(format!("read `{}` error: {}", local.display(), err));
+    }
+    let mut file = file.unwrap();
+    if let Err(err) = file.seek(std::io::SeekFrom::Start(index * part_size as u64)).await {
+        return Err(format!("read `{}` error: {}", local.display(), err));
+    }
+    let mut part: Vec<u8> = Vec::with_capacity(part_size);
+    if let Err(err) = file.take(part_size as u64).read_to_end(&mut part).await {
+        return Err(format!("read `{}` error: {}", local.display(), err));
+    }
+    Ok(part)
+}
+
+fn find_xml_value(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = xml[start..].find(&format!("</{}>", tag))? + start;
+    Some(xml[start..end].to_string())
+}
+
+impl S3Client {
+    pub fn new(
+        endpoint: &str,
+        bucket: &str,
+        region: &str,
+        access_key_id: &str,
+        secret_access_key: &str,
+    ) -> S3Client {
+        S3Client {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: bucket.to_string(),
+            region: if region.is_empty() {
+                DEFAULT_REGION.to_string()
+            } else {
+                region.to_string()
+            },
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn host(&self) -> String {
+        let without_scheme = self.endpoint.splitn(2, "://").last().unwrap_or("");
+        without_scheme.split('/').next().unwrap_or("").to_string()
+    }
+
+    // AWS signature version 4 for one request
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        key: &str,
+        query: &[(String, String)],
+        content_type: Option<&str>,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response, String> {
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = sha256_hex(&body);
+        let canonical_uri = format!("/{}/{}", uri_encode(&self.bucket, true), uri_encode(key, false));
+        let canonical_query = canonical_query(query);
+        let host = self.host();
+        let (canonical_request, signed_headers) = canonical_request(
+            method.as_str(),
+            &canonical_uri,
+            &canonical_query,
+            &[("host", &host), ("x-amz-content-sha256", &payload_hash), ("x-amz-date", &amz_date)],
+            &payload_hash,
+        );
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, SERVICE);
+        let signature = signature(&self.secret_access_key, &amz_date, &self.region, SERVICE, &canonical_request);
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id, scope, signed_headers, signature
+        );
+
+        let mut url = format!("{}{}", self.endpoint, canonical_uri);
+        if !canonical_query.is_empty() {
+            url = format!("{}?{}", url, canonical_query);
+        }
+        let mut builder = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header("Authorization", authorization);
+        if let Some(content_type) = content_type {
+            builder = builder.header("Content-Type", content_type);
+        }
+        let result = builder.body(body).send().await;
+        if let Err(err) = result {
+            return Err(err.to_string());
+        }
+        let response = result.unwrap();
+        if !response.status().is_success() && response.status() != reqwest::StatusCode::NOT_FOUND {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("s3 `{}` {}: {}", key, status, text));
+        }
+        Ok(response)
+    }
+
+    // etag without quotes, None when the object does not exist
+    pub async fn head_etag(&self, key: &str) -> Result<Option<String>, String> {
+        let response = self.send(reqwest::Method::HEAD, key, &[], None, Vec::new()).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(response
+            .headers()
+            .get("ETag")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim_matches('"').to_string()))
+    }
+
+    pub async fn put_object(&self, key: &str, content_type: &str, body: Vec<u8>) -> Result<(), String> {
+        let response = self
+            .send(reqwest::Method::PUT, key, &[], Some(content_type), body)
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(format!("bucket `{}` not found", self.bucket));
+        }
+        Ok(())
+    }
+
+    // `local` goes up in parts of `part_size`, only one part is in memory at a time
+    pub async fn put_multipart(
+        &self,
+        key: &str,
+        content_type: &str,
+        local: &Path,
+        size: u64,
+        part_size: usize,
+    ) -> Result<(), String> {
+        let uploads = vec![(String::from("uploads"), String::new())];
+        let response = self
+            .send(reqwest::Method::POST, key, &uploads, Some(content_type), Vec::new())
+            .await?;
+        let xml = response.text().await.unwrap_or_default();
+        let upload_id = match find_xml_value(&xml, "UploadId") {
+            Some(upload_id) => upload_id,
+            None => return Err(format!("create multipart upload of `{}` failed: {}", key, xml)),
+        };
+        let mut complete = String::from("<CompleteMultipartUpload>");
+        let parts = (size + part_size as u64 - 1) / part_size as u64;
+        for index in 0..parts {
+            let part = read_part(local, index, part_size).await;
+            if let Err(err) = part {
+                let abort = vec![(String::from("uploadId"), upload_id.clone())];
+                _ = self.send(reqwest::Method::DELETE, key, &abort, None, Vec::new()).await;
+                return Err(err);
+            }
+            let query = vec![
+                (String::from("partNumber"), (index + 1).to_string()),
+                (String::from("uploadId"), upload_id.clone()),
+            ];
+            let response = self
+                .send(reqwest::Method::PUT, key, &query, None, part.unwrap())
+                .await;
+            let etag = response.ok().and_then(|r| {
+                r.headers()
+                    .get("ETag")
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_string())
+            });
+            if etag.is_none() {
+                let abort = vec![(String::from("uploadId"), upload_id.clone())];
+                _ = self.send(reqwest::Method::DELETE, key, &abort, None, Vec::new()).await;
+                return Err(format!("upload part {} of `{}` failed", index + 1, key));
+            }
+            complete.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                index + 1,
+                etag.unwrap()
+            ));
+        }
+        complete.push_str("</CompleteMultipartUpload>");
+        let query = vec![(String::from("uploadId"), upload_id)];
+        let response = self
+            .send(
+                reqwest::Method::POST,
+                key,
+                &query,
+                Some("application/xml"),
+                complete.into_bytes(),
+            )
+            .await?;
+        // errors of complete multipart upload come back with status 200
+        let xml = response.text().await.unwrap_or_default();
+        if xml.contains("<Error>") {
+            return Err(format!("complete multipart upload of `{}` failed: {}", key, xml));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        canonical_query, canonical_request, multipart_etag, sha256_hex, signature, signing_key, uri_encode,
+    };
+    use std::io::Cursor;
+
+    // the examples of the AWS signature version 4 documentation
+    const SECRET: &str = "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY";
+    const AMZ_DATE: &str = "20130524T000000Z";
+    const HOST: &str = "examplebucket.s3.amazonaws.com";
+    const EMPTY_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    fn s3_signature(
+        method: &str,
+        uri: &str,
+        query: &[(String, String)],
+        headers: &[(&str, &str)],
+        payload_hash: &str,
+    ) -> String {
+        let (request, _) = canonical_request(method, uri, &canonical_query(query), headers, payload_hash);
+        signature(SECRET, AMZ_DATE, "us-east-1", "s3", &request)
+    }
+
+    #[test]
+    fn signing_key_iam_example() {
+        let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+        assert_eq!(hex::encode(key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+    }
+
+    #[test]
+    fn sigv4_get_vanilla() {
+        let amz_date = "20150830T123600Z";
+        let (request, signed_headers) = canonical_request(
+            "GET",
+            "/",
+            "",
+            &[("Host", "example.amazonaws.com"), ("X-Amz-Date", amz_date)],
+            EMPTY_HASH,
+        );
+        assert_eq!(signed_headers, "host;x-amz-date");
+        assert_eq!(
+            signature("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", amz_date, "us-east-1", "service", &request),
+            "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn s3_get_object() {
+        let headers = [
+            ("x-amz-date", AMZ_DATE),
+            ("host", HOST),
+            ("range", "bytes=0-9"),
+            ("x-amz-content-sha256", EMPTY_HASH),
+        ];
+        assert_eq!(
+            s3_signature("GET", "/test.txt", &[], &headers, EMPTY_HASH),
+            "f0e8bdb87c964420e857bd35b5d6ed310bd44f0170aba48dd91039c6036bdb41"
+        );
+    }
+
+    #[test]
+    fn s3_put_object() {
+        let payload_hash = sha256_hex(b"Welcome to Amazon S3.");
+        assert_eq!(payload_hash, "44ce7dd67c959e0d3524ffac1771dfbba87d2b6b4b4e99e42034a8b803f8b072");
+        let headers = [
+            ("Date", "Fri, 24 May 2013 00:00:00 GMT"),
+            ("Host", HOST),
+            ("x-amz-content-sha256", payload_hash.as_str()),
+            ("x-amz-date", AMZ_DATE),
+            ("x-amz-storage-class", "REDUCED_REDUNDANCY"),
+        ];
+        let uri = format!("/{}", uri_encode("test$file.text", false));
+        assert_eq!(
+            s3_signature("PUT", &uri, &[], &headers, &payload_hash),
+            "98ad721746da40c64f1a55b78f14c238d841ea1380cd77a1b5971af0ece108bd"
+        );
+    }
+
+    #[test]
+    fn s3_query_signatures() {
+        let headers = [("host", HOST), ("x-amz-content-sha256", EMPTY_HASH), ("x-amz-date", AMZ_DATE)];
+        let lifecycle = vec![(String::from("lifecycle"), String::new())];
+        assert_eq!(
+            s3_signature("GET", "/", &lifecycle, &headers, EMPTY_HASH),
+            "fea454ca298b7da1c68078a5d1bdbfbbe0d65c699e0f91ac7a200a0136783543"
+        );
+        // keys are sorted into the canonical query
+        let list = vec![
+            (String::from("prefix"), String::from("J")),
+            (String::from("max-keys"), String::from("2")),
+        ];
+        assert_eq!(canonical_query(&list), "max-keys=2&prefix=J");
+        assert_eq!(
+            s3_signature("GET", "/", &list, &headers, EMPTY_HASH),
+            "34b48302e7b5fa45bde8084f4b7868a86f0a534bc59db6670ed5711ef69dc6f7"
+        );
+    }
+
+    #[test]
+    fn etag_of_parts() {
+        assert_eq!(
+            multipart_etag(Cursor::new(b"abcdefghij"), 4).unwrap(),
+            "446feba4c1b5cc7ad93bf4d44a0e36ac-3"
+        );
+        let data: Vec<u8> = (0..100).flat_map(|_| 0..=255u8).collect();
+        assert_eq!(
+            multipart_etag(Cursor::new(data), 8192).unwrap(),
+            "8c0302278e462c244aeab65993e3d76a-4"
+        );
+    }
+}
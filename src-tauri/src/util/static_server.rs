@@ -0,0 +1,235 @@
+use crate::util::mime::mime_type;
+use std::fs::File;
+use std::io::{copy, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+const MAX_HEADER_LINES: usize = 100;
+
+struct Request {
+    method: String,
+    path: String,
+    range: Option<String>,
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                out.push(b);
+                i = i + 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i = i + 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<Request> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let path = percent_decode(target.split(['?', '#']).next().unwrap_or("/"));
+    let mut range = None;
+    for _ in 0..MAX_HEADER_LINES {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 {
+            break;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("range") {
+                range = Some(value.trim().to_string());
+            }
+        }
+    }
+    Some(Request {
+        method,
+        path,
+        range,
+    })
+}
+
+// maps an url path into root, refusing anything that climbs out of it
+fn resolve(root: &Path, url_path: &str) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+    for component in Path::new(url_path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    if path.is_dir() {
+        path.push("index.html");
+    }
+    Some(path)
+}
+
+// `bytes=a-b`, `bytes=a-` or `bytes=-n`, only a single range is supported
+fn parse_range(range: &str, size: u64) -> Option<(u64, u64)> {
+    // nothing of an empty file can be satisfied
+    if size == 0 {
+        return None;
+    }
+    let spec = range.strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = if start.is_empty() {
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 {
+            return None;
+        }
+        (size.saturating_sub(suffix), size - 1)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end: u64 = if end.is_empty() {
+            size - 1
+        } else {
+            end.parse::<u64>().ok()?.min(size - 1)
+        };
+        (start, end)
+    };
+    if start > end || start >= size {
+        return None;
+    }
+    Some((start, end))
+}
+
+fn write_status(stream: &mut TcpStream, status: &str, headers: &Vec<String>) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {}\r\n", status);
+    for header in headers.iter() {
+        head.push_str(header);
+        head.push_str("\r\n");
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    stream.write_all(head.as_bytes())
+}
+
+fn handle(mut stream: TcpStream, root: &Path) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = match read_request(&mut reader) {
+        Some(request) => request,
+        None => return Ok(()),
+    };
+    if request.method != "GET" && request.method != "HEAD" {
+        return write_status(&mut stream, "405 Method Not Allowed", &vec![String::from("Allow: GET, HEAD")]);
+    }
+    let path = match resolve(root, &request.path) {
+        Some(path) if path.is_file() => path,
+        _ => return write_status(&mut stream, "404 Not Found", &vec![String::from("Content-Length: 0")]),
+    };
+    let mut file = File::open(&path)?;
+    let size = file.metadata()?.len();
+    let mut headers = vec![
+        format!("Content-Type: {}", mime_type(path.to_str().unwrap_or(""))),
+        String::from("Accept-Ranges: bytes"),
+        String::from("Cache-Control: no-cache"),
+        String::from("Access-Control-Allow-Origin: *"),
+    ];
+    let (status, start, length) = match request.range {
+        Some(range) => match parse_range(&range, size) {
+            Some((start, end)) => {
+                headers.push(format!("Content-Range: bytes {}-{}/{}", start, end, size));
+                ("206 Partial Content", start, end - start + 1)
+            }
+            None => {
+                headers.push(format!("Content-Range: bytes */{}", size));
+                headers.push(String::from("Content-Length: 0"));
+                return write_status(&mut stream, "416 Range Not Satisfiable", &headers);
+            }
+        },
+        None => ("200 OK", 0, size),
+    };
+    headers.push(format!("Content-Length: {}", length));
+    write_status(&mut stream, status, &headers)?;
+    if request.method == "HEAD" {
+        return Ok(());
+    }
+    file.seek(SeekFrom::Start(start))?;
+    copy(&mut file.take(length), &mut stream)?;
+    stream.flush()
+}
+
+// serves files of `root` until `stop` is set, one thread per connection
+pub fn serve(listener: TcpListener, root: PathBuf, stop: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        if let Ok(stream) = stream {
+            let root = root.clone();
+            thread::spawn(move || {
+                _ = handle(stream, &root);
+            });
+        }
+    }
+}
+
+// the accept loop only notices `stop` on the next connection
+pub fn wake(port: u16) {
+    _ = TcpStream::connect(("127.0.0.1", port));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_range;
+
+    #[test]
+    fn range_bounded() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=500-2000", 1000), Some((500, 999)));
+    }
+
+    #[test]
+    fn range_open_ended() {
+        assert_eq!(parse_range("bytes=100-", 1000), Some((100, 999)));
+        assert_eq!(parse_range("bytes=999-", 1000), Some((999, 999)));
+    }
+
+    #[test]
+    fn range_suffix() {
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-5000", 1000), Some((0, 999)));
+        assert_eq!(parse_range("bytes=-0", 1000), None);
+    }
+
+    #[test]
+    fn range_out_of_range() {
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=1000-1200", 1000), None);
+        assert_eq!(parse_range("bytes=50-10", 1000), None);
+    }
+
+    #[test]
+    fn range_empty_file() {
+        assert_eq!(parse_range("bytes=0-", 0), None);
+        assert_eq!(parse_range("bytes=-10", 0), None);
+        assert_eq!(parse_range("bytes=0-0", 0), None);
+    }
+
+    #[test]
+    fn range_unsupported() {
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+        assert_eq!(parse_range("bytes=a-b", 1000), None);
+    }
+}
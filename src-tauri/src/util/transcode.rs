@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+pub const FORMAT_JPEG: &str = "jpeg";
+pub const FORMAT_WEBP: &str = "webp";
+pub const FORMAT_PNG: &str = "png";
+
+// how faces and textures are re-encoded, decoding and encoding again drops exif
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TranscodeProfile {
+    // format of the faces, `jpeg` or `webp`
+    #[serde(default = "default_format")]
+    pub format: String,
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+    // longest edge in pixels, never upscaled
+    pub max_edge: Option<u32>,
+    #[serde(default = "default_true")]
+    pub faces: bool,
+    #[serde(default = "default_true")]
+    pub textures: bool,
+}
+
+fn default_format() -> String {
+    String::from(FORMAT_JPEG)
+}
+
+fn default_quality() -> u8 {
+    85
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn extension(format: &str) -> &'static str {
+    match format {
+        FORMAT_WEBP => "webp",
+        FORMAT_PNG => "png",
+        _ => "jpg",
+    }
+}
+
+pub fn transcoded_name(name: &str, format: &str) -> String {
+    if name.is_empty() {
+        return String::new();
+    }
+    let stem = match name.rfind('.') {
+        Some(dot) if !name[dot..].contains('/') => &name[..dot],
+        _ => name,
+    };
+    format!("{}.{}", stem, extension(format))
+}
+
+pub fn format_from_name(name: &str) -> Option<&'static str> {
+    let lower = name.to_lowercase();
+    if lower.ends_with(".jpg") || lower.ends_with(".jpeg") {
+        return Some(FORMAT_JPEG);
+    }
+    if lower.ends_with(".png") {
+        return Some(FORMAT_PNG);
+    }
+    if lower.ends_with(".webp") {
+        return Some(FORMAT_WEBP);
+    }
+    None
+}
@@ -0,0 +1,4 @@
+// signed cdn urls change on every page load, only the path tells a new file
+pub fn strip_query(url: &str) -> &str {
+    url.split(|c| c == '?' || c == '#').next().unwrap_or(url)
+}
@@ -102,7 +102,8 @@ class App extends React.Component {
             return
         }
         
-        let data = await invoke.addDownloadWorkTask(realPath, workJSON)
+        let source = this.state.dataType == 'vr_link' ? this.state.vrData : undefined
+        let data = await invoke.addDownloadWorkTask(realPath, workJSON, undefined, source, this.state.saveName)
         if (data.state == "failure") {
             Message.error(data.message)
             return
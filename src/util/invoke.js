@@ -90,10 +90,13 @@ var fileExists = async (filePath) => {
     return result
 }
 
-var addDownloadWorkTask = async (dir, work_json) => {
+var addDownloadWorkTask = async (dir, work_json, selection, source, name) => {
     let result = await invoke('add_work_download_task', {
         dir: dir,
         workJson: work_json,
+        selection: selection,
+        source: source,
+        name: name,
     })
     return result
 }
@@ -192,11 +195,324 @@ var newRemoteDirectory = async (host, privateKeyPath, path) => {
     return result
 }
 
+var convertEquirectToCube = async (images, dir, size) => {
+    let result = await invoke('convert_equirect_to_cube', {
+        images, dir, size
+    })
+    return result
+}
+
+
+var buildLocalWork = async (dir, panoramaDir, modelFile, positionsFile, faceSize) => {
+    let result = await invoke('build_local_work', {
+        dir, panoramaDir, modelFile, positionsFile, faceSize
+    })
+    return result
+}
+
+
+var exportModelGLB = async (dir, dest) => {
+    let result = await invoke('export_model_glb', {
+        dir, dest
+    })
+    return result
+}
+
+
+var exportObserverPoses = async (dir, dest, format, faceSize) => {
+    let result = await invoke('export_observer_poses', {
+        dir, dest, format, faceSize
+    })
+    return result
+}
+
+
+var analyzeWorkGraph = async (dir) => {
+    let result = await invoke('analyze_work_graph', {
+        dir
+    })
+    return result
+}
+
+
+var exportWorkGraph = async (dir, dest, format) => {
+    let result = await invoke('export_work_graph', {
+        dir, dest, format
+    })
+    return result
+}
+
+
+var generateWalkthrough = async (dir, from, to, interval, loopTour) => {
+    let result = await invoke('generate_walkthrough', {
+        dir, from, to, interval, loopTour
+    })
+    return result
+}
+
+
+var splitWorkByFloor = async (dir, destDir) => {
+    let result = await invoke('split_work_by_floor', {
+        dir, destDir
+    })
+    return result
+}
+
+
+var mergeWorks = async (sources, destDir) => {
+    let result = await invoke('merge_works', {
+        sources, destDir
+    })
+    return result
+}
+
+
+var exportWorkForHosting = async (dir, baseUrl, dest) => {
+    let result = await invoke('export_work_for_hosting', {
+        dir, baseUrl, dest
+    })
+    return result
+}
+
+
+var listPublishProfiles = async () => {
+    let result = await invoke('list_publish_profiles', {})
+    return result
+}
+
+
+var savePublishProfile = async (profile) => {
+    let result = await invoke('save_publish_profile', {
+        profile
+    })
+    return result
+}
+
+
+var deletePublishProfile = async (name) => {
+    let result = await invoke('delete_publish_profile', {
+        name
+    })
+    return result
+}
+
+
+var publishWorkSFTP = async (dir, profile, source, threads) => {
+    let result = await invoke('publish_work_sftp', {
+        dir, profile, source, threads
+    })
+    return result
+}
+
+
+var publishWorkS3 = async (dir, profile, source, threads) => {
+    let result = await invoke('publish_work_s3', {
+        dir, profile, source, threads
+    })
+    return result
+}
+
+
+var startPreviewServer = async (dir, source, port, open) => {
+    let result = await invoke('start_preview_server', {
+        dir, source, port, open
+    })
+    return result
+}
+
+
+var openPreviewServer = async (port, target) => {
+    let result = await invoke('open_preview_server', {
+        port, target
+    })
+    return result
+}
+
+
+var stopPreviewServer = async (port) => {
+    let result = await invoke('stop_preview_server', {
+        port
+    })
+    return result
+}
+
+
+var listPreviewServers = async () => {
+    let result = await invoke('list_preview_servers', {})
+    return result
+}
+
+
+var convertWorkLayout = async (dir, layout) => {
+    let result = await invoke('convert_work_layout', {
+        dir, layout
+    })
+    return result
+}
+
+
+var estimateSingleHTML = async (dir) => {
+    let result = await invoke('estimate_single_html', {
+        dir
+    })
+    return result
+}
+
+
+var exportSingleHTML = async (dir, dest, maxBytes) => {
+    let result = await invoke('export_single_html', {
+        dir, dest, maxBytes
+    })
+    return result
+}
+
+
+var transcodeWorkImages = async (dir, profile) => {
+    let result = await invoke('transcode_work_images', {
+        dir, profile
+    })
+    return result
+}
+
+
+var generateWorkThumbnails = async (dir, mode, size) => {
+    let result = await invoke('generate_work_thumbnails', {
+        dir, mode, size
+    })
+    return result
+}
+
+
+var setWorkBranding = async (dir, branding) => {
+    let result = await invoke('set_work_branding', {
+        dir, branding
+    })
+    return result
+}
+
+
+var addWorkRedactions = async (dir, redactions) => {
+    let result = await invoke('add_work_redactions', {
+        dir, redactions
+    })
+    return result
+}
+
+
+var listWorkRedactions = async (dir) => {
+    let result = await invoke('list_work_redactions', {
+        dir
+    })
+    return result
+}
+
+
+var reapplyWorkRedactions = async (dir) => {
+    let result = await invoke('reapply_work_redactions', {
+        dir
+    })
+    return result
+}
+
+
+var listViewerTemplates = async () => {
+    let result = await invoke('list_viewer_templates', {})
+    return result
+}
+
+
+var setWorkViewer = async (dir, config) => {
+    let result = await invoke('set_work_viewer', {
+        dir, config
+    })
+    return result
+}
+
+
+var rebuildWorkPreview = async (dir) => {
+    let result = await invoke('rebuild_work_preview', {
+        dir
+    })
+    return result
+}
+
+
+var listLibraryWorks = async (filter) => {
+    let result = await invoke('list_library_works', {
+        filter
+    })
+    return result
+}
+
+
+var renameLibraryWork = async (id, name) => {
+    let result = await invoke('rename_library_work', {
+        id, name
+    })
+    return result
+}
+
+
+var relocateLibraryWork = async (id, dir, moveFiles) => {
+    let result = await invoke('relocate_library_work', {
+        id, dir, moveFiles
+    })
+    return result
+}
+
+
+var removeLibraryWork = async (id, deleteFiles) => {
+    let result = await invoke('remove_library_work', {
+        id, deleteFiles
+    })
+    return result
+}
+
+
+var rescanLibraryWorks = async (roots) => {
+    let result = await invoke('rescan_library_works', {
+        roots
+    })
+    return result
+}
+
+
+var getWorkSource = async (dir) => {
+    let result = await invoke('get_work_source', {
+        dir
+    })
+    return result
+}
+
+
+var checkWorkUpdates = async (dir, workJson) => {
+    let result = await invoke('check_work_updates', {
+        dir, workJson
+    })
+    return result
+}
+
+
+var syncWork = async (dir, workJson, snapshot) => {
+    let result = await invoke('sync_work', {
+        dir, workJson, snapshot
+    })
+    return result
+}
+
+
+var diffWorks = async (left, right) => {
+    let result = await invoke('diff_works', {
+        left, right
+    })
+    return result
+}
+
 
 export {
-    writeFile, readFile, readDir, simpleReadDir, setWindowTitle, uploadFile, createFile, createDir, deleteFile, deleteFolder, renameFile, fileExists, addDownloadWorkTask, queryDownloadTask, addProjectDownload, queryProjectDownloadTask, parseJSCode, parseHTMLTitle, getLocalConfig, updateOuterHost, listFiles, downloadRemoteFile, uploadRemoteFile, deleteRemoteFile, newRemoteDirectory
+    writeFile, readFile, readDir, simpleReadDir, setWindowTitle, uploadFile, createFile, createDir, deleteFile, deleteFolder, renameFile, fileExists, addDownloadWorkTask, queryDownloadTask, addProjectDownload, queryProjectDownloadTask, parseJSCode, parseHTMLTitle, getLocalConfig, updateOuterHost, listFiles, downloadRemoteFile, uploadRemoteFile, deleteRemoteFile, newRemoteDirectory, convertEquirectToCube, buildLocalWork, exportModelGLB, exportObserverPoses, analyzeWorkGraph, exportWorkGraph, generateWalkthrough, splitWorkByFloor, mergeWorks, exportWorkForHosting, listPublishProfiles, savePublishProfile, deletePublishProfile, publishWorkSFTP, publishWorkS3, startPreviewServer, openPreviewServer, stopPreviewServer, listPreviewServers, convertWorkLayout, estimateSingleHTML, exportSingleHTML, transcodeWorkImages, generateWorkThumbnails, setWorkBranding, addWorkRedactions, listWorkRedactions, reapplyWorkRedactions, listViewerTemplates, setWorkViewer, rebuildWorkPreview, listLibraryWorks, renameLibraryWork, relocateLibraryWork, removeLibraryWork, rescanLibraryWorks, getWorkSource, checkWorkUpdates, syncWork, diffWorks
 }
 
 export default {
-    writeFile, readFile, readDir, simpleReadDir, setWindowTitle, uploadFile, createFile, createDir, deleteFile, deleteFolder, renameFile, fileExists, addDownloadWorkTask, queryDownloadTask, addProjectDownload, queryProjectDownloadTask, parseJSCode, parseHTMLTitle, getLocalConfig, updateOuterHost, listFiles, downloadRemoteFile, uploadRemoteFile, deleteRemoteFile, newRemoteDirectory
+    writeFile, readFile, readDir, simpleReadDir, setWindowTitle, uploadFile, createFile, createDir, deleteFile, deleteFolder, renameFile, fileExists, addDownloadWorkTask, queryDownloadTask, addProjectDownload, queryProjectDownloadTask, parseJSCode, parseHTMLTitle, getLocalConfig, updateOuterHost, listFiles, downloadRemoteFile, uploadRemoteFile, deleteRemoteFile, newRemoteDirectory, convertEquirectToCube, buildLocalWork, exportModelGLB, exportObserverPoses, analyzeWorkGraph, exportWorkGraph, generateWalkthrough, splitWorkByFloor, mergeWorks, exportWorkForHosting, listPublishProfiles, savePublishProfile, deletePublishProfile, publishWorkSFTP, publishWorkS3, startPreviewServer, openPreviewServer, stopPreviewServer, listPreviewServers, convertWorkLayout, estimateSingleHTML, exportSingleHTML, transcodeWorkImages, generateWorkThumbnails, setWorkBranding, addWorkRedactions, listWorkRedactions, reapplyWorkRedactions, listViewerTemplates, setWorkViewer, rebuildWorkPreview, listLibraryWorks, renameLibraryWork, relocateLibraryWork, removeLibraryWork, rescanLibraryWorks, getWorkSource, checkWorkUpdates, syncWork, diffWorks
 }
@@ -147,9 +147,22 @@ var getWorkJSONByURL = async (url) => {
     return ''
 }
 
+// refetch the page a work was downloaded from and download what changed since
+var syncWorkFromSource = async (dir, snapshot) => {
+    let source = await invoke.getWorkSource(dir)
+    if (!source.url) {
+        return null
+    }
+    let workJSON = await getWorkJSONByURL(source.url)
+    if (!workJSON) {
+        return null
+    }
+    return await invoke.syncWork(dir, JSON.stringify(workJSON), snapshot)
+}
+
 export default {
-    getWorkJSONByURL
+    getWorkJSONByURL, syncWorkFromSource
 }
 export {
-    getWorkJSONByURL
+    getWorkJSONByURL, syncWorkFromSource
 }
\ No newline at end of file